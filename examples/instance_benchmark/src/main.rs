@@ -0,0 +1,136 @@
+// Draws 100k instances offscreen with different amounts of them changing every frame, on the CPU or the GPU, and reports
+// how long frames take
+use std::time::Instant;
+
+use hello_wgpu::{
+    animation::AnimationPass,
+    app::{AppData, RenderTarget},
+    camera::Camera,
+    instance::Instance,
+    object::Object,
+    render_pass::{phong::PhongPass, RenderPass},
+    resources,
+    texture::Texture,
+};
+
+const GRID_SIZE: (usize, usize, usize) = (100, 100, 10); // 100k instances
+const SPACING: f32 = 3.0;
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const WARMUP_FRAMES: u32 = 10;
+const DEFAULT_FRAMES: u32 = 100; // Timed frames per scenario, unless given on the command line
+// The instancing example's resources double as the benchmark's
+const RES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../instancing");
+
+fn instances() -> Vec<Instance> {
+    let (width, depth, height) = GRID_SIZE;
+    let offset = cgmath::Vector3::new(width as f32, height as f32, depth as f32) * SPACING / 2.0;
+    (0..width).flat_map(|x| (0..depth).flat_map(move |z| (0..height).map(move |y| {
+        let position = cgmath::Vector3::new(x as f32, y as f32, z as f32) * SPACING - offset;
+        Instance { position, rotation_speed: 30.0, ..Default::default() }
+    }))).collect()
+}
+
+// Which instances are rotated each frame, and where
+#[derive(Copy, Clone)]
+enum Changes {
+    None,
+    EveryNth(usize), // On the CPU, then uploaded
+    Rebuild, // All of them on the CPU, then uploaded into a new buffer, as every frame used to before buffers were kept
+    Gpu, // All of them, by `AnimationPass`
+}
+
+// Milliseconds per frame with `changes` made each frame, as (CPU time to update the instances and record the frame, total
+// time including submitting it and waiting for the GPU). Instance uploads are part of the CPU time
+fn time_frames(
+    app_data: &AppData,
+    phong_pass: &mut PhongPass,
+    animation_pass: &mut AnimationPass,
+    objects: &mut Vec<Object>,
+    depth_texture: &Texture,
+    changes: Changes,
+    frames: u32,
+) -> (f64, f64) {
+    let RenderTarget::Offscreen(target) = &app_data.target else {
+        unreachable!("headless AppData always renders offscreen");
+    };
+    let mut draw_frame = |objects: &mut Vec<Object>| {
+        let start = Instant::now();
+        if let Changes::EveryNth(n) = changes {
+            let num_instances = objects[0].instances().len();
+            for i in (0..num_instances).step_by(n) {
+                objects[0].instance_mut(i).update(1.0 / 60.0);
+            }
+        }
+        if let Changes::Rebuild = changes {
+            for instance in objects[0].instances_mut() {
+                instance.update(1.0 / 60.0);
+            }
+            // With no buffer to keep, `draw` creates a new one holding every instance
+            phong_pass.instance_buffers.clear();
+        }
+        let mut encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Benchmark Encoder"),
+        });
+        if let Changes::Gpu = changes {
+            phong_pass.update_instance_buffers(app_data, objects);
+            animation_pass.dispatch(app_data, &mut encoder, objects, &phong_pass.instance_buffers, 1.0 / 60.0);
+        }
+        let encoder = phong_pass.draw(app_data, &target.view, encoder, objects, Some(depth_texture)).unwrap();
+        let command_buffer = encoder.finish();
+        let cpu_time = start.elapsed();
+        // Software adapters draw the whole frame in here
+        app_data.queue.submit(std::iter::once(command_buffer));
+        for object in objects.iter_mut() {
+            object.clear_dirty();
+        }
+        app_data.device.poll(wgpu::Maintain::Wait);
+        cpu_time
+    };
+
+    for _ in 0..WARMUP_FRAMES.min(frames) {
+        draw_frame(objects);
+    }
+    let start = Instant::now();
+    let cpu_time = (0..frames).map(|_| draw_frame(objects)).sum::<std::time::Duration>();
+    let to_ms_per_frame = |time: std::time::Duration| time.as_secs_f64() * 1000.0 / frames as f64;
+    (to_ms_per_frame(cpu_time), to_ms_per_frame(start.elapsed()))
+}
+
+fn main() {
+    env_logger::init();
+    let frames = std::env::args().nth(1).map_or(DEFAULT_FRAMES, |arg| arg.parse().expect("Number of frames should be a whole number"));
+
+    let app_data = pollster::block_on(AppData::new_headless(WIDTH, HEIGHT, wgpu::TextureFormat::Rgba8UnormSrgb)).unwrap();
+    // Far enough back to see every instance, so culling doesn't skew the results
+    let camera = Camera::new(
+        cgmath::Point3::new(0.0, 250.0, 350.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        WIDTH as f32 / HEIGHT as f32,
+        45.0,
+        1.0,
+        1000.0,
+    );
+    let mut phong_pass = PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+    let mut animation_pass = AnimationPass::new(&app_data.device).unwrap();
+    let depth_texture = Texture::create_depth_texture(&app_data.device, &app_data.config, "Benchmark Depth Texture");
+    let model = pollster::block_on(resources::load_model(
+        "cube.obj", &app_data.device, &app_data.queue, &phong_pass.texture_bind_group_layout, Some(RES_DIR),
+    )).unwrap();
+    let mut objects = vec![Object::new(model, instances())];
+    println!("{} instances on {}", objects[0].instances().len(), app_data.adapter_info.name);
+
+    let scenarios = [
+        ("none", Changes::None),
+        ("1%", Changes::EveryNth(100)),
+        ("all", Changes::EveryNth(1)),
+        ("all (rebuilt)", Changes::Rebuild),
+        ("all (GPU)", Changes::Gpu),
+    ];
+    for (name, changes) in scenarios {
+        let (cpu_time, frame_time) = time_frames(&app_data, &mut phong_pass, &mut animation_pass, &mut objects, &depth_texture, changes, frames);
+        println!("{:>13} changing: {:>8.2} ms/frame on the CPU, {:>8.2} ms/frame in total", name, cpu_time, frame_time);
+    }
+    println!("{} drawn, {} culled", phong_pass.culling_stats.drawn_instances, phong_pass.culling_stats.culled_instances);
+}
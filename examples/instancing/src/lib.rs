@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[cfg(target_arch="wasm32")]
@@ -6,25 +7,57 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::js_sys::Math::random;
 
 use hello_wgpu::{
+    animation,
     app,
+    error::Error,
     camera,
+    camera_bookmarks,
+    camera_path,
     texture,
     render_pass,
     object,
     resources,
     instance,
+    scene,
     gui,
+    timestep,
+};
+use gui::windows::{
+    bookmarks::{AddBookmarkEvent, BookmarksEvent, RemoveBookmarkEvent, RestoreBookmarkEvent},
+    performance::PerformanceEvent,
+    stats::StatsEvent,
 };
-use gui::windows::{performance::PerformanceEvent, stats::StatsEvent};
 use instance::Instance;
 use render_pass::RenderPass;
 
 use cgmath::prelude::*;
 use cgmath::Rotation3;
-use winit::{event::WindowEvent, event_loop::EventLoop};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, WindowEvent},
+    event_loop::EventLoop,
+    keyboard::{KeyCode, PhysicalKey},
+};
 use rand::Rng;
 use serde;
 
+// Degrees per second the light circles the scene by
+const LIGHT_ORBIT_SPEED: f32 = 300.0;
+// Seconds between keyframes recorded with K
+const KEYFRAME_SPACING: f32 = 2.0;
+const CAMERA_PATH_FILE: &str = "camera_path.json";
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+// Seconds the camera takes to move to a bookmark
+const BOOKMARK_TRANSITION_TIME: f32 = 1.0;
+// Pixels the cursor can move between pressing and releasing the mouse button for it to still count as a click rather than a drag
+const CLICK_TOLERANCE: f64 = 4.0;
+// How much bigger the selected song's cube is drawn
+const SELECTED_SCALE: f32 = 1.5;
+// Degrees per second the songs turn by as a whole, while turning is switched on with G
+const SONGS_TURN_SPEED: f32 = 10.0;
+// The lightbulb model is far bigger than the rest of the scene
+const LIGHTBULB_SCALE: f32 = 0.0025;
+
 #[derive(serde::Deserialize, Debug)]
 struct Song {
     path: String,
@@ -39,9 +72,31 @@ struct State {
     basic_objects: Vec<object::Object>,
     phong_pass: render_pass::phong::PhongPass,
     phong_objects: Vec<object::Object>,
+    animation_pass: Option<animation::AnimationPass>, // Spins the phong objects on the GPU, where the device can
+    animation_time: f32, // Seconds of updates the animation pass has still to catch up on
+    scene: scene::SceneGraph,
+    light_orbit_node: scene::NodeId, // Turns to carry the light round the scene
+    light_node: scene::NodeId, // The light itself, with the lightbulb attached
+    songs_node: scene::NodeId, // Every song's cube is attached to this
+    is_turning_songs: bool,
     depth_texture: texture::Texture,
+    id_pass: render_pass::id::IdPass, // Only drawn when a song is being picked
+    id_texture: texture::Texture,
+    id_depth_texture: texture::Texture,
     camera: camera::Camera,
-    camera_controller: camera::CameraController,
+    orbit_camera_controller: camera::OrbitCameraController,
+    fly_camera_controller: camera::FlyCameraController,
+    is_flying: bool, // Which of the camera controllers is in use, toggled with Tab
+    camera_path_player: camera_path::CameraPathPlayer, // Overrides the camera controllers while playing
+    camera_bookmarks: camera_bookmarks::CameraBookmarks,
+    bookmark_transition: Option<camera_path::CameraPathPlayer>, // Moving to a bookmark, overrides everything else while playing
+    songs: HashMap<object::InstanceHandle, Song>, // By the handle of each song's cube
+    selected_song: Option<object::InstanceHandle>,
+    removed_songs: Vec<(Song, Instance)>, // Taken out with Delete, until R puts them back
+    cursor_position: Option<PhysicalPosition<f64>>,
+    click_start: Option<PhysicalPosition<f64>>, // Where the left mouse button went down, if it's held
+    pick_position: Option<PhysicalPosition<f64>>, // Where a click is waiting for the next frame to draw the IDs
+    id_readback: Option<render_pass::id::IdReadback>, // The ID under the click, on its way back from the GPU
 }
 
 impl State {
@@ -59,35 +114,34 @@ impl State {
             100.0,
         );
         
-        let camera_controller = camera::CameraController::new(0.2);
+        let orbit_camera_controller = camera::OrbitCameraController::new(0.005, 1.0, 50.0).with_inertia(0.1);
+        let fly_camera_controller = camera::FlyCameraController::new(12.0, 0.003);
 
         let basic_pass = render_pass::basic::BasicPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
         let phong_pass = render_pass::phong::PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+        let id_pass = render_pass::id::IdPass::new(&app_data.device, &camera);
+        let animation_pass = animation::AnimationPass::new(&app_data.device)
+            .map_err(|e| log::warn!("Animating on the CPU: {}", e))
+            .ok();
 
         // Load models
         let light_model = resources::load_model("lightbulb_2.obj", &app_data.device, &app_data.queue, &phong_pass.texture_bind_group_layout, Some(env!("OUT_DIR"))).await.unwrap();
         let cube_model = resources::load_model("cube.obj", &app_data.device, &app_data.queue, &phong_pass.texture_bind_group_layout, Some(env!("OUT_DIR"))).await.unwrap();
         let ferris_model = resources::load_model("ferris.obj", &app_data.device, &app_data.queue, &phong_pass.texture_bind_group_layout, Some(env!("OUT_DIR"))).await.unwrap();
         
-        // Set up instances for basic pass
-        let light_instance = vec![{
-            let position = cgmath::Vector3::from(basic_pass.light_uniform.position);
-            let rotation = if position.is_zero() {
-                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-            } else {
-                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(0.0))
-            };
-            let rotation_speed: f32 = 0.0;
-            Instance { position, rotation, rotation_speed }
-        }];
+        // Set up instances for basic pass. The lightbulb sits at the origin of the light node it's attached to below
+        let light_instance = vec![
+            Instance { scale: cgmath::Vector3::new(LIGHTBULB_SCALE, LIGHTBULB_SCALE, LIGHTBULB_SCALE), ..Default::default() },
+        ];
         let basic_objects = vec![
-            object::Object{ model: light_model, instances: light_instance },
+            object::Object::new(light_model, light_instance),
         ];
         // Set up instances for phong pass
-        // Note: if new instances are added at runtime, both `instance_buffer` and `camera_bind_group` must be recreated
+        // Songs can be taken out and put back while running, see `remove_selected_song`
         let songs: Vec<Song> = resources::load_json::<Song>("coords.json", Some(env!("OUT_DIR"))).await.unwrap();
         const SPACE_BETWEEN: f32 = 5.0;
         let mut rng = rand::thread_rng();
+        let genre_tints = genre_tints(&songs);
         let cube_instances = songs.iter().map(|song| {
             let position = cgmath::Vector3 { x: song.x * SPACE_BETWEEN, y: song.y * SPACE_BETWEEN, z: song.z * SPACE_BETWEEN };
             let rotation = if position.is_zero() {
@@ -95,8 +149,9 @@ impl State {
             } else {
                 cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
             };
-            let rotation_speed: f32 = rng.gen_range(-0.5..0.5);
-            Instance { position, rotation, rotation_speed }
+            let rotation_speed: f32 = rng.gen_range(-30.0..30.0);
+            let tint = genre_tints[song.tagged_genre.as_str()];
+            Instance { position, rotation, rotation_speed, tint, ..Default::default() }
         }).collect::<Vec<_>>();
         let ferris_instance = vec![{
             let position = cgmath::Vector3 { x: 1.0, y: 1.0, z: 1.0 };
@@ -105,125 +160,662 @@ impl State {
             } else {
                 cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(0.0))
             };
-            let rotation_speed: f32 = rng.gen_range(-0.5..0.5);
-            Instance { position, rotation, rotation_speed }
+            let rotation_speed: f32 = rng.gen_range(-30.0..30.0);
+            Instance { position, rotation, rotation_speed, ..Default::default() }
         }];
         let phong_objects = vec![
-            object::Object{ model: cube_model, instances: cube_instances },
-            object::Object{ model: ferris_model, instances: ferris_instance },
+            object::Object::new(cube_model, cube_instances),
+            object::Object::new(ferris_model, ferris_instance),
         ];
+
+        // Set up the scene graph, so the lightbulb goes wherever the light does and the songs can be turned as one
+        let mut scene = scene::SceneGraph::new();
+        let light_orbit_node = scene.add_node(None, scene::Transform::default());
+        let light_node = scene.add_node(Some(light_orbit_node), scene::Transform {
+            position: basic_pass.light_uniform.position.into(),
+            ..Default::default()
+        });
+        scene.attach(light_node, &basic_objects[0], basic_objects[0].handle_at(0));
+        let songs_node = scene.add_node(None, scene::Transform::default());
+        let songs = songs.into_iter().enumerate()
+            .map(|(i, song)| (phong_objects[0].handle_at(i), song))
+            .collect::<HashMap<_, _>>();
+        for handle in songs.keys() {
+            scene.attach(songs_node, &phong_objects[0], *handle);
+        }
+
         let depth_texture = texture::Texture::create_depth_texture(&app_data.device, &app_data.config, "Depth Texture");
+        let id_texture = texture::Texture::create_id_texture(&app_data.device, &app_data.config, "ID Texture");
+        let id_depth_texture = texture::Texture::create_depth_texture(&app_data.device, &app_data.config, "ID Depth Texture");
 
-        app_data.egui_renderer.add_gui_window("performance", Box::new(gui::windows::performance::PerformanceWindow::new()));
-        app_data.egui_renderer.add_gui_window("stats", Box::new(gui::windows::stats::StatsWindow::new()));
+        // There won't be any bookmarks until the first one is saved
+        let camera_bookmarks = match camera_bookmarks::CameraBookmarks::load(BOOKMARKS_FILE, Some(env!("OUT_DIR"))).await {
+            Ok(camera_bookmarks) => camera_bookmarks,
+            Err(Error::ResourceNotFound { .. }) => camera_bookmarks::CameraBookmarks::new(),
+            Err(e) => {
+                log::error!("{}", e);
+                camera_bookmarks::CameraBookmarks::new()
+            }
+        };
         
         Self {
             basic_pass,
             basic_objects,
             phong_pass,
             phong_objects,
+            animation_pass,
+            animation_time: 0.0,
+            scene,
+            light_orbit_node,
+            light_node,
+            songs_node,
+            is_turning_songs: false,
             depth_texture,
+            id_pass,
+            id_texture,
+            id_depth_texture,
             camera,
-            camera_controller,
+            orbit_camera_controller,
+            fly_camera_controller,
+            is_flying: false,
+            camera_path_player: camera_path::CameraPathPlayer::new(camera_path::CameraPath::new()).with_looping(true),
+            camera_bookmarks,
+            bookmark_transition: None,
+            songs,
+            selected_song: None,
+            removed_songs: Vec::new(),
+            cursor_position: None,
+            click_start: None,
+            pick_position: None,
+            id_readback: None,
         }
     }
 }
 
-fn window_event(
-    app_data: &mut app::AppData,
-    state: &mut State,
-    window_event: &WindowEvent,
-) {
-    match window_event {
-        _ => {
-            state.camera_controller.process_events(window_event);
-        }
-    }
+// A colour for every genre, spread evenly around the colour wheel. Genres are taken in alphabetical order, so each keeps
+// its colour from run to run
+fn genre_tints(
+    songs: &[Song],
+) -> HashMap<&str, [f32; 4]> {
+    let mut genres = songs.iter().map(|song| song.tagged_genre.as_str()).collect::<Vec<_>>();
+    genres.sort_unstable();
+    genres.dedup();
+    let num_genres = genres.len();
+    genres.into_iter().enumerate().map(|(i, genre)| {
+        let hue = i as f32 / num_genres as f32;
+        // Pastel rather than fully saturated, so the cube's texture still shows through
+        let [r, g, b] = [0.0, 2.0 / 3.0, 1.0 / 3.0].map(|offset: f32| {
+            let channel = ((hue + offset).fract() * 6.0 - 3.0).abs() - 1.0;
+            0.4 + 0.6 * channel.clamp(0.0, 1.0)
+        });
+        (genre, [r, g, b, 1.0])
+    }).collect()
 }
 
-fn resize(
-    app_data: &mut app::AppData,
-    state: &mut State,
-    size: (u32, u32),
-) {
-    state.depth_texture = texture::Texture::create_depth_texture(&app_data.device, &app_data.config, "depth_texture");
+// Which bookmark a number key restores, 1 being the first
+fn bookmark_index(
+    key: KeyCode,
+) -> Option<usize> {
+    let digits = [
+        KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+        KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    digits.iter().position(|digit| *digit == key)
 }
 
-fn update(
-    app_data: &mut app::AppData,
-    state: &mut State,
-) {
-    // Move instances
-    for object in state.phong_objects.iter_mut() {
-        object.instances = object.instances.iter().map(|instance| {
-            let position = instance.position;
-            let rotation_speed = instance.rotation_speed;
-            let rotation = instance.rotation * cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(rotation_speed));
-            Instance { position, rotation, rotation_speed }
-        }).collect::<Vec<_>>();
+impl State {
+    fn set_projection(
+        &mut self,
+        app_data: &app::AppData,
+        projection: camera::Projection,
+    ) {
+        log::info!("Switching to {:?} projection", projection);
+        self.camera.set_projection(projection);
+        self.phong_pass.update_projection(&app_data.device, projection);
+        self.basic_pass.update_projection(&app_data.device, projection);
+        self.id_pass.update_projection(&app_data.device, projection);
+    }
+
+    // Selects the song under the cursor when the left mouse button is clicked, but not when it's dragged (e.g. to orbit the camera)
+    fn handle_click(
+        &mut self,
+        app_data: &app::AppData,
+        window_event: &WindowEvent,
+    ) {
+        match window_event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::MouseInput { button: MouseButton::Left, state: ElementState::Pressed, .. } => {
+                let gui_wants_pointer_input = app_data.egui_renderer.as_ref()
+                    .is_some_and(|egui_renderer| egui_renderer.wants_pointer_input());
+                self.click_start = if gui_wants_pointer_input { None } else { self.cursor_position };
+            }
+            WindowEvent::MouseInput { button: MouseButton::Left, state: ElementState::Released, .. } => {
+                let (Some(start), Some(end)) = (self.click_start.take(), self.cursor_position) else {
+                    return;
+                };
+                if (end.x - start.x).hypot(end.y - start.y) <= CLICK_TOLERANCE {
+                    self.pick_position = Some(end);
+                }
+            }
+            _ => {},
+        }
+    }
+
+    // Selects the song whose cube was under the click, once its ID has made it back from the GPU
+    fn finish_pick(
+        &mut self,
+        app_data: &app::AppData,
+    ) {
+        let Some(id_readback) = &self.id_readback else {
+            return;
+        };
+        let std::task::Poll::Ready(picked) = id_readback.poll(&app_data.device) else {
+            return;
+        };
+        self.id_readback = None;
+        // The cubes are the first phong object, one instance per song. Songs removed since the IDs were drawn may have
+        // moved another cube to the index, or left it past the end
+        let cubes = &self.phong_objects[0];
+        let selected_song = match picked {
+            Some((0, instance_index)) if instance_index < cubes.instances().len() => {
+                let handle = cubes.handle_at(instance_index);
+                let song = &self.songs[&handle];
+                log::info!("Selected {:?} ({})", song.path, song.tagged_genre);
+                Some(handle)
+            }
+            _ => None,
+        };
+        self.select_song(selected_song);
+    }
+
+    // Enlarges the selected song's cube, and puts the previously selected one back to its normal size
+    fn select_song(
+        &mut self,
+        selected_song: Option<object::InstanceHandle>,
+    ) {
+        let cubes = &mut self.phong_objects[0];
+        if let Some(previous) = self.selected_song.and_then(|handle| cubes.get_mut(handle)) {
+            previous.scale = cgmath::Vector3::new(1.0, 1.0, 1.0);
+        }
+        if let Some(selected) = selected_song.and_then(|handle| cubes.get_mut(handle)) {
+            selected.scale = cgmath::Vector3::new(SELECTED_SCALE, SELECTED_SCALE, SELECTED_SCALE);
+        }
+        self.selected_song = selected_song;
+    }
+
+    // Takes the selected song's cube out of the scene. The last cube moves into its place, so only that one is uploaded again
+    fn remove_selected_song(
+        &mut self,
+    ) {
+        let Some(handle) = self.selected_song else {
+            return;
+        };
+        self.select_song(None);
+        let instance = self.phong_objects[0].remove_instance(handle);
+        if let (Some(instance), Some(song)) = (instance, self.songs.remove(&handle)) {
+            log::info!("Removed {:?}", song.path);
+            self.removed_songs.push((song, instance));
+        }
+    }
+
+    // Puts every removed song back, with new handles
+    fn restore_removed_songs(
+        &mut self,
+    ) {
+        let cubes = &mut self.phong_objects[0];
+        for (song, instance) in self.removed_songs.drain(..) {
+            let handle = cubes.push_instance(instance);
+            self.scene.attach(self.songs_node, cubes, handle);
+            self.songs.insert(handle, song);
+        }
+    }
+
+    fn restore_bookmark(
+        &mut self,
+        index: usize,
+    ) {
+        self.bookmark_transition = self.camera_bookmarks.transition_to(index, &mut self.camera, BOOKMARK_TRANSITION_TIME);
+    }
+
+    // Saves the bookmarks and shows the new list in the GUI
+    fn bookmarks_changed(
+        &self,
+        app_data: &mut app::AppData,
+    ) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.camera_bookmarks.save(BOOKMARKS_FILE, Some(env!("OUT_DIR"))) {
+            log::error!("{}", e);
+        }
+        self.send_bookmarks(app_data);
+    }
+
+    fn send_bookmarks(
+        &self,
+        app_data: &mut app::AppData,
+    ) {
+        if let Some(egui_renderer) = &mut app_data.egui_renderer {
+            let names = self.camera_bookmarks.bookmarks().iter().map(|bookmark| bookmark.name.clone()).collect();
+            if let Err(e) = egui_renderer.send_event("bookmarks", &BookmarksEvent { names }) {
+                log::error!("{}", e);
+            }
+        }
     }
 
-    // Move lights
-    let light_position: cgmath::Vector3<_> = state.phong_pass.light_uniform.position.into();
-    state.phong_pass.light_uniform.position = (
-        cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(5.0)) * light_position
-    ).into();
-    state.basic_pass.light_uniform.position = (
-        cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(5.0)) * light_position
-    ).into();
-
-    // Move camera
-    state.camera_controller.update_camera(&mut state.camera);
-    state.phong_pass.camera_uniform.update_view_proj(&state.camera);
-    state.basic_pass.camera_uniform.update_view_proj(&state.camera);
-
-    // Update GUI
-    app_data.egui_renderer.send_event(
-        "performance", 
-        &PerformanceEvent {
-            fps: app_data.fps,
-            render_time: app_data.render_time,
-            update_time: app_data.update_time,
-        }
-    );
-    app_data.egui_renderer.send_event(
-        "stats", 
-        &StatsEvent {
-            num_instances: state.phong_objects.iter().map(|object| object.instances.len() as u32).sum()
-        }
-    );
+    // K records a keyframe, L plays or pauses the recorded path, and F5/F9 save and load it
+    fn camera_path_key(
+        &mut self,
+        key: KeyCode,
+    ) {
+        let player = &mut self.camera_path_player;
+        match key {
+            KeyCode::KeyK => {
+                let time = if player.path.keyframes().is_empty() { 0.0 } else { player.path.duration() + KEYFRAME_SPACING };
+                player.path.record(time, &self.camera);
+                log::info!("Recorded camera keyframe {} at {}s", player.path.keyframes().len(), time);
+            }
+            KeyCode::KeyL => {
+                player.toggle_play();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            KeyCode::F5 => {
+                match player.path.save(CAMERA_PATH_FILE, Some(env!("OUT_DIR"))) {
+                    Ok(()) => log::info!("Saved camera path to {:?}", resources::resource_path(CAMERA_PATH_FILE, Some(env!("OUT_DIR")))),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            KeyCode::F9 => {
+                match pollster::block_on(camera_path::CameraPath::load(CAMERA_PATH_FILE, Some(env!("OUT_DIR")))) {
+                    Ok(path) => *player = camera_path::CameraPathPlayer::new(path).with_looping(true),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            _ => {},
+        }
+    }
+
+    // The orthographic view height that shows the target at the same size as the perspective projection would,
+    // so switching projections doesn't jump and zooming the orbit camera still works
+    fn orthographic_size(
+        &self,
+    ) -> f32 {
+        2.0 * self.camera.eye().distance(self.camera.target()) * (self.camera.fovy().to_radians() / 2.0).tan()
+    }
 }
 
-fn render(
-    app_data: &mut app::AppData,
-    state: &mut State,
-    view: wgpu::TextureView,
-    mut encoder: wgpu::CommandEncoder,
-) {
-    encoder = state.phong_pass.draw(
-        app_data,
-        &view,
-        encoder,
-        &state.phong_objects,
-        Some(&state.depth_texture),
-    ).unwrap();
-    encoder = state.basic_pass.draw(
-        app_data,
-        &view,
-        encoder,
-        &state.basic_objects,
-        Some(&state.depth_texture),
-    ).unwrap();
-
-    app_data.egui_renderer.draw(
-        &app_data.device,
-        &app_data.queue,
-        &mut encoder,
-        &view,
-    );
-
-    // `Queue.submit()` will accept anything that implements `IntoIter`, so we wrap `encoder.finish()` up in `std::iter::once`
-    app_data.queue.submit(std::iter::once(encoder.finish()));
+impl app::Application for State {
+    fn init(
+        &mut self,
+        app_data: &mut app::AppData,
+    ) {
+        // A fixed rate keeps the simulation deterministic. Nothing here blends between updates with `interpolation_alpha`
+        // though, so at display rates that aren't a multiple of 60 Hz things move a little unevenly
+        app_data.update_mode = timestep::UpdateMode::Fixed(timestep::FixedTimestep::new(60.0));
+
+        if let Some(egui_renderer) = &mut app_data.egui_renderer {
+            egui_renderer.add_gui_window("performance", Box::new(gui::windows::performance::PerformanceWindow::new()));
+            egui_renderer.add_gui_window("stats", Box::new(gui::windows::stats::StatsWindow::new()));
+            egui_renderer.add_gui_window("present_mode", Box::new(gui::windows::present_mode::PresentModeWindow::new()));
+            egui_renderer.add_gui_window("adapter", Box::new(gui::windows::adapter::AdapterWindow::new(app_data.adapter_info.clone())));
+            egui_renderer.add_gui_window("bookmarks", Box::new(gui::windows::bookmarks::BookmarksWindow::new()));
+        }
+        self.send_bookmarks(app_data);
+    }
+
+    fn gui_event(
+        &mut self,
+        app_data: &mut app::AppData,
+        event: &gui::SendAny,
+    ) {
+        if let Some(AddBookmarkEvent { name }) = event.downcast_ref() {
+            self.camera_bookmarks.add(name, &self.camera);
+            self.bookmarks_changed(app_data);
+        } else if let Some(RestoreBookmarkEvent { index }) = event.downcast_ref() {
+            self.restore_bookmark(*index);
+        } else if let Some(RemoveBookmarkEvent { index }) = event.downcast_ref() {
+            self.camera_bookmarks.remove(*index);
+            self.bookmarks_changed(app_data);
+        }
+    }
+
+    fn reinit(
+        &mut self,
+        app_data: &mut app::AppData,
+    ) {
+        // Everything on the GPU went with the lost device, so rebuild the scene from scratch but keep looking at it from the same place
+        let mut state = pollster::block_on(State::new(app_data));
+        std::mem::swap(&mut state.camera, &mut self.camera);
+        std::mem::swap(&mut state.orbit_camera_controller, &mut self.orbit_camera_controller);
+        std::mem::swap(&mut state.fly_camera_controller, &mut self.fly_camera_controller);
+        state.is_flying = self.is_flying;
+        state.is_turning_songs = self.is_turning_songs;
+        *state.scene.transform_mut(state.songs_node) = *self.scene.transform(self.songs_node);
+        std::mem::swap(&mut state.camera_path_player, &mut self.camera_path_player);
+        std::mem::swap(&mut state.camera_bookmarks, &mut self.camera_bookmarks);
+        // The scene is rebuilt with every song, so removed songs come back. Handles don't carry over to the new objects, so
+        // the selected song is found again by its path
+        let selected_path = self.selected_song.and_then(|handle| self.songs.get(&handle)).map(|song| song.path.as_str());
+        let selected_song = selected_path.and_then(|path| {
+            state.songs.iter().find(|(_, song)| song.path == path).map(|(handle, _)| *handle)
+        });
+        state.select_song(selected_song);
+        *self = state;
+    }
+
+    fn window_event(
+        &mut self,
+        app_data: &mut app::AppData,
+        window_event: &WindowEvent,
+    ) {
+        self.handle_click(app_data, window_event);
+        let was_mouse_look_active = self.fly_camera_controller.is_mouse_look_active();
+        match window_event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::Tab),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.is_flying = !self.is_flying;
+                // Stop whichever controller we're leaving from holding on to a grabbed cursor
+                app_data.set_cursor_grab(false);
+                self.fly_camera_controller = camera::FlyCameraController::new(self.fly_camera_controller.speed(), 0.003);
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyP),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                let projection = match self.camera.projection() {
+                    camera::Projection::Perspective => camera::Projection::InfinitePerspective,
+                    camera::Projection::InfinitePerspective => camera::Projection::Orthographic { size: self.orthographic_size() },
+                    camera::Projection::Orthographic { .. } => camera::Projection::Perspective,
+                };
+                self.set_projection(app_data, projection);
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(key @ (KeyCode::KeyK | KeyCode::KeyL | KeyCode::F5 | KeyCode::F9)),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.camera_path_key(*key);
+                return;
+            }
+            // Number keys move to the first nine bookmarks, B saves a new one
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(key),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } if bookmark_index(*key).is_some() => {
+                self.restore_bookmark(bookmark_index(*key).unwrap());
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyB),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                let name = format!("Bookmark {}", self.camera_bookmarks.bookmarks().len() + 1);
+                self.camera_bookmarks.add(&name, &self.camera);
+                self.bookmarks_changed(app_data);
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyG),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.is_turning_songs = !self.is_turning_songs;
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::Delete),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.remove_selected_song();
+                return;
+            }
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                self.restore_removed_songs();
+                return;
+            }
+            // Look straight along an axis with an orthographic projection, so the songs read like a 2D scatter plot
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(key @ (KeyCode::KeyX | KeyCode::KeyY | KeyCode::KeyZ)),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+                ..
+            } => {
+                let direction = match key {
+                    KeyCode::KeyX => cgmath::Vector3::unit_x(),
+                    // Not quite straight down, as the camera can't look exactly along its up vector
+                    KeyCode::KeyY => cgmath::Vector3::new(0.0, 1.0, 0.001).normalize(),
+                    _ => cgmath::Vector3::unit_z(),
+                };
+                let target = self.camera.target();
+                self.camera.set_eye(target + direction * self.camera.eye().distance(target));
+                self.set_projection(app_data, camera::Projection::Orthographic { size: self.orthographic_size() });
+                return;
+            }
+            _ => {
+                let gui_wants_pointer_input = app_data.egui_renderer.as_ref()
+                    .is_some_and(|egui_renderer| egui_renderer.wants_pointer_input());
+                if self.is_flying {
+                    self.fly_camera_controller.process_events(window_event, gui_wants_pointer_input);
+                } else {
+                    self.orbit_camera_controller.process_events(window_event, gui_wants_pointer_input);
+                }
+            }
+        }
+        // Hide and lock the cursor while looking around with the mouse
+        if self.fly_camera_controller.is_mouse_look_active() != was_mouse_look_active {
+            app_data.set_cursor_grab(self.fly_camera_controller.is_mouse_look_active());
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _app_data: &mut app::AppData,
+        device_event: &DeviceEvent,
+    ) {
+        if self.is_flying {
+            self.fly_camera_controller.process_device_events(device_event);
+        }
+    }
+
+    // `App` keeps the camera's aspect ratio in step with the window
+    fn camera(
+        &mut self,
+    ) -> Option<&mut camera::Camera> {
+        Some(&mut self.camera)
+    }
+
+    fn resize(
+        &mut self,
+        app_data: &mut app::AppData,
+        _size: (u32, u32),
+    ) {
+        self.depth_texture = texture::Texture::create_depth_texture(&app_data.device, &app_data.config, "depth_texture");
+        self.id_texture = texture::Texture::create_id_texture(&app_data.device, &app_data.config, "ID Texture");
+        self.id_depth_texture = texture::Texture::create_depth_texture(&app_data.device, &app_data.config, "ID Depth Texture");
+    }
+
+    fn update(
+        &mut self,
+        app_data: &mut app::AppData,
+    ) {
+        let delta_time = app_data.update_delta_time as f32;
+
+        self.finish_pick(app_data);
+
+        // Move instances. That happens on the GPU if it can, but only once the next frame is drawn
+        if self.animation_pass.is_some() {
+            self.animation_time += delta_time;
+        } else {
+            for object in self.phong_objects.iter_mut() {
+                for instance in object.instances_mut() {
+                    instance.update(delta_time);
+                }
+            }
+        }
+
+        // Move lights, and the songs as a whole if they're turning
+        let light_orbit = self.scene.transform_mut(self.light_orbit_node);
+        light_orbit.rotation = (cgmath::Quaternion::from_angle_y(cgmath::Deg(LIGHT_ORBIT_SPEED * delta_time)) * light_orbit.rotation).normalize();
+        if self.is_turning_songs {
+            let songs = self.scene.transform_mut(self.songs_node);
+            songs.rotation = (cgmath::Quaternion::from_angle_y(cgmath::Deg(SONGS_TURN_SPEED * delta_time)) * songs.rotation).normalize();
+        }
+        self.scene.update(self.phong_objects.iter_mut().chain(self.basic_objects.iter_mut()));
+        let light_position = self.scene.world_position(self.light_node);
+        self.phong_pass.light_uniform.position = light_position.into();
+        self.basic_pass.light_uniform.position = light_position.into();
+
+        // Move camera
+        if let Some(bookmark_transition) = &mut self.bookmark_transition {
+            bookmark_transition.update(delta_time, &mut self.camera);
+            if !bookmark_transition.is_playing() {
+                self.bookmark_transition = None;
+            }
+        } else if self.camera_path_player.is_playing() {
+            self.camera_path_player.update(delta_time, &mut self.camera);
+        } else if self.is_flying {
+            self.fly_camera_controller.update_camera(&mut self.camera, delta_time);
+        } else {
+            self.orbit_camera_controller.update_camera(&mut self.camera, delta_time);
+        }
+        if let camera::Projection::Orthographic { .. } = self.camera.projection() {
+            self.camera.set_projection(camera::Projection::Orthographic { size: self.orthographic_size() });
+        }
+        self.phong_pass.camera_uniform.update_view_proj(&self.camera);
+        self.basic_pass.camera_uniform.update_view_proj(&self.camera);
+        self.id_pass.camera_uniform.update_view_proj(&self.camera);
+
+        // Update GUI
+        if let Some(egui_renderer) = &mut app_data.egui_renderer {
+            let result = egui_renderer.send_event(
+                "performance", 
+                &PerformanceEvent {
+                    fps: app_data.fps,
+                    render_time: app_data.render_time,
+                    update_time: app_data.update_time,
+                }
+            ).and_then(|_| egui_renderer.send_event(
+                "stats", 
+                &StatsEvent {
+                    num_instances: self.phong_objects.iter().map(|object| object.instances().len() as u32).sum(),
+                    // From the last frame, as this frame hasn't been drawn yet
+                    drawn_instances: self.phong_pass.culling_stats.drawn_instances,
+                    culled_instances: self.phong_pass.culling_stats.culled_instances,
+                }
+            ));
+            if let Err(e) = result {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    fn render(
+        &mut self,
+        app_data: &mut app::AppData,
+        view: wgpu::TextureView,
+        mut encoder: wgpu::CommandEncoder,
+    ) {
+        if let Some(animation_pass) = &mut self.animation_pass {
+            self.phong_pass.update_instance_buffers(app_data, &self.phong_objects);
+            animation_pass.dispatch(app_data, &mut encoder, &self.phong_objects, &self.phong_pass.instance_buffers, self.animation_time);
+            self.animation_time = 0.0;
+        }
+        encoder = self.phong_pass.draw(
+            app_data,
+            &view,
+            encoder,
+            &self.phong_objects,
+            Some(&self.depth_texture),
+        ).unwrap();
+        encoder = self.basic_pass.draw(
+            app_data,
+            &view,
+            encoder,
+            &self.basic_objects,
+            Some(&self.depth_texture),
+        ).unwrap();
+        if self.pick_position.is_some() {
+            // The animated instances are only where the GPU has put them, so the IDs have to be drawn from the same buffers
+            encoder = self.id_pass.draw_with_instance_buffers(
+                app_data,
+                &self.id_texture.view,
+                encoder,
+                &self.phong_objects,
+                Some(&self.id_depth_texture),
+                &self.phong_pass.instance_buffers,
+            ).unwrap();
+        }
+
+        if let Some(egui_renderer) = &mut app_data.egui_renderer {
+            egui_renderer.draw(
+                &app_data.device,
+                &app_data.queue,
+                &mut encoder,
+                &view,
+            );
+        }
+
+        // `Queue.submit()` will accept anything that implements `IntoIter`, so we wrap `encoder.finish()` up in `std::iter::once`
+        app_data.queue.submit(std::iter::once(encoder.finish()));
+        // Every pass has uploaded this frame's changes now
+        for object in self.phong_objects.iter_mut().chain(self.basic_objects.iter_mut()) {
+            object.clear_dirty();
+        }
+
+        // The IDs can only be copied out once they've been drawn
+        if let Some(position) = self.pick_position.take() {
+            self.id_readback = Some(self.id_pass.read_id(&app_data.device, &app_data.queue, &self.id_texture, position.x as u32, position.y as u32));
+        }
+    }
 }
 
 #[cfg_attr(target_arch="wasm32", wasm_bindgen(start))]
@@ -240,15 +832,8 @@ pub async fn run() {
     
     let event_loop = EventLoop::new().unwrap();
     let window = Arc::new(app::create_window("cubes-app", &event_loop));
-    let mut app_data = app::AppData::new(Arc::clone(&window)).await;
+    let mut app_data = app::AppData::new(Arc::clone(&window)).await.unwrap();
     let state = State::new(&mut app_data).await;
-    let app = app::App::new(
-        state,
-        app_data,
-        window_event,
-        resize,
-        update,
-        render,
-    ).await;
+    let app = app::App::new(state, app_data).await;
     app.run(window, event_loop);
 }
@@ -0,0 +1,374 @@
+// Moving instances on the GPU. Each object's positions and rotations live in a storage buffer that a compute shader moves
+// on every frame, writing the resulting `RawInstance`s straight into the instance buffers a render pass draws from, so
+// animated instances never make the round trip through the CPU. Needs compute shaders, so isn't available on WebGL
+use std::collections::{hash_map::Entry, HashMap};
+use crate::{
+    app::AppData,
+    error::{Error, Result},
+    instance::{linear_part, Instance, InstanceBuffer},
+    object::{InstanceOrigin, Object, ObjectId},
+};
+use wgpu::util::DeviceExt;
+
+// Invocations per workgroup, matching `@workgroup_size` in animation.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+// States, properties and the instance buffer being written to
+const STORAGE_BUFFERS: u32 = 3;
+
+// The part of an instance the GPU moves on, so owns once the instance has been uploaded
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceState {
+    position: [f32; 3],
+    _padding: u32, // `rotation` is aligned to 16 bytes in WGSL
+    rotation: [f32; 4], // (x, y, z) vector then w
+}
+
+// The part of an instance only the CPU changes, uploaded again whenever the object marks the instance as dirty
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceProperties {
+    parent_transform: [[f32; 4]; 4],
+    parent_normal: [[f32; 4]; 3], // The inverse-transpose of `parent_transform`'s rotation and scale, columns padded to 16 bytes
+    velocity: [f32; 3],
+    rotation_speed: f32,
+    scale: [f32; 3],
+    _padding: u32, // `tint` is aligned to 16 bytes in WGSL
+    tint: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AnimationParams {
+    delta_time: f32,
+    num_instances: u32,
+    _padding: [u32; 2], // Uniform buffers are at least 16 bytes
+}
+
+impl From<&Instance> for InstanceState {
+    fn from(instance: &Instance) -> Self {
+        Self {
+            position: instance.position.into(),
+            _padding: 0,
+            rotation: [instance.rotation.v.x, instance.rotation.v.y, instance.rotation.v.z, instance.rotation.s],
+        }
+    }
+}
+
+impl From<&Instance> for InstanceProperties {
+    fn from(instance: &Instance) -> Self {
+        use cgmath::{Matrix, SquareMatrix};
+        // Worked out here, as WGSL has no matrix inverse
+        let parent_linear = linear_part(&instance.parent_transform);
+        let parent_normal = parent_linear.invert().map_or(parent_linear, |inverse| inverse.transpose());
+        let pad = |column: cgmath::Vector3<f32>| [column.x, column.y, column.z, 0.0];
+        Self {
+            parent_transform: instance.parent_transform.into(),
+            parent_normal: [pad(parent_normal.x), pad(parent_normal.y), pad(parent_normal.z)],
+            velocity: instance.velocity.into(),
+            rotation_speed: instance.rotation_speed,
+            scale: instance.scale.into(),
+            _padding: 0,
+            tint: instance.tint,
+        }
+    }
+}
+
+// Animates every instance of the objects it's given by their `velocity` and `rotation_speed`, as `Instance::update` does on
+// the CPU, within their `parent_transform`. From the first `dispatch` on, the GPU owns each instance's position and
+// rotation: changing them on the CPU does nothing, other than for instances added since the last `dispatch` (or all of
+// them after `Object::set_instances`), and instances moved by `Object::remove_instance` take theirs with them. Everything
+// else still follows the object's dirty instances.
+// Frustum culling and CPU picking go by the CPU's positions, so turn off `Object::frustum_culling` for objects given a
+// velocity, and pick them with `IdPass` instead
+pub struct AnimationPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    animations: HashMap<ObjectId, Animation>,
+}
+
+impl AnimationPass {
+    // Fails with `Error::Unsupported` on devices without compute shaders or enough storage buffers, such as WebGL2 and
+    // other downlevel devices, which have to animate instances on the CPU instead
+    pub fn new(
+        device: &wgpu::Device,
+    ) -> Result<Self> {
+        let limits = device.limits();
+        if limits.max_storage_buffers_per_shader_stage < STORAGE_BUFFERS
+            || limits.max_compute_invocations_per_workgroup < WORKGROUP_SIZE
+            || limits.max_compute_workgroup_size_x < WORKGROUP_SIZE
+        {
+            return Err(Error::Unsupported("compute shaders with storage buffers"));
+        }
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Animation Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/animation.wgsl").into()),
+        });
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Animation Bind Group Layout"),
+            entries: &[
+                // Params
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, false), // States
+                storage_entry(2, true), // Properties
+                storage_entry(3, false), // Raw instances
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Animation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Animation Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        Ok(Self {
+            pipeline,
+            bind_group_layout,
+            animations: HashMap::new(),
+        })
+    }
+
+    // Moves every instance of `objects` on by `delta_time` seconds, writing the results into their buffers in
+    // `instance_buffers`. Those must already be up to date with `objects` (see `PhongPass::update_instance_buffers`), and
+    // the pass drawing from them must come after this in `encoder`. Call this exactly once between each
+    // `Object::clear_dirty`, as calling it again would move the states of moved instances a second time
+    pub fn dispatch(
+        &mut self,
+        app_data: &AppData,
+        encoder: &mut wgpu::CommandEncoder,
+        objects: &[Object],
+        instance_buffers: &HashMap<ObjectId, InstanceBuffer>,
+        delta_time: f32,
+    ) {
+        self.animations.retain(|id, _| objects.iter().any(|object| object.id() == *id));
+        for object in objects {
+            match self.animations.entry(object.id()) {
+                Entry::Occupied(mut entry) => entry.get_mut().update(app_data, encoder, object),
+                Entry::Vacant(entry) => {
+                    entry.insert(Animation::new(app_data, object));
+                }
+            }
+        }
+
+        // The instance buffers may have been reallocated since the last frame, so the bind groups are made afresh
+        let mut bind_groups = Vec::new();
+        for object in objects {
+            let animation = &self.animations[&object.id()];
+            if animation.len == 0 {
+                continue; // Bindings can't be empty
+            }
+            let params = AnimationParams {
+                delta_time,
+                num_instances: animation.len as u32,
+                _padding: [0; 2],
+            };
+            app_data.queue.write_buffer(&animation.params_buffer, 0, bytemuck::cast_slice(&[params]));
+            let bind_group = app_data.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Animation Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: animation.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: animation.state_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: animation.properties_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: instance_buffers[&object.id()].buffer().as_entire_binding(),
+                    },
+                ],
+            });
+            bind_groups.push((bind_group, animation.len as u32));
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Animation Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        for (bind_group, num_instances) in &bind_groups {
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(num_instances.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+}
+
+// One object's buffers in `AnimationPass`. Like `InstanceBuffer`, these grow geometrically
+struct Animation {
+    params_buffer: wgpu::Buffer,
+    state_buffer: wgpu::Buffer,
+    properties_buffer: wgpu::Buffer,
+    capacity: usize, // In instances
+    len: usize,
+}
+
+impl Animation {
+    fn new(
+        app_data: &AppData,
+        object: &Object,
+    ) -> Self {
+        let params_buffer = app_data.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Animation Params Buffer"),
+            size: std::mem::size_of::<AnimationParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instances = object.instances();
+        // Storage bindings can't be empty either, so there's always room for at least one instance
+        let capacity = instances.len().max(1);
+        let animation = Self {
+            params_buffer,
+            state_buffer: create_buffer::<InstanceState>(&app_data.device, "Animation State Buffer", capacity),
+            properties_buffer: create_buffer::<InstanceProperties>(&app_data.device, "Animation Properties Buffer", capacity),
+            capacity,
+            len: instances.len(),
+        };
+        write::<InstanceState>(&app_data.queue, &animation.state_buffer, instances, 0..instances.len());
+        write::<InstanceProperties>(&app_data.queue, &animation.properties_buffer, instances, 0..instances.len());
+        animation
+    }
+
+    fn update(
+        &mut self,
+        app_data: &AppData,
+        encoder: &mut wgpu::CommandEncoder,
+        object: &Object,
+    ) {
+        let instances = object.instances();
+        let previous_len = self.len;
+        self.len = instances.len();
+        let state_size = std::mem::size_of::<InstanceState>() as wgpu::BufferAddress;
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().max(self.capacity * 2);
+            let state_buffer = create_buffer::<InstanceState>(&app_data.device, "Animation State Buffer", self.capacity);
+            // Keep the states the GPU has moved on so far
+            if previous_len > 0 {
+                encoder.copy_buffer_to_buffer(&self.state_buffer, 0, &state_buffer, 0, previous_len as wgpu::BufferAddress * state_size);
+            }
+            self.state_buffer = state_buffer;
+            self.properties_buffer = create_buffer::<InstanceProperties>(&app_data.device, "Animation Properties Buffer", self.capacity);
+            write::<InstanceProperties>(&app_data.queue, &self.properties_buffer, instances, 0..instances.len());
+        } else {
+            // Instances that were added or moved are dirty too
+            for dirty in object.dirty_ranges() {
+                write::<InstanceProperties>(&app_data.queue, &self.properties_buffer, instances, dirty);
+            }
+        }
+
+        // Moved instances take their states with them, and new ones start from where the CPU put them. It all goes through
+        // `encoder`, in order, as `Queue::write_buffer` would land before the moves had read the states it overwrote
+        let origins = object.instance_origins();
+        let moves = origins.iter()
+            .filter_map(|(index, origin)| match origin {
+                InstanceOrigin::Index(from) if from != index => Some((*from, *index)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !moves.is_empty() {
+            // A buffer can't be copied within itself, so the states go by way of another buffer. Every move reads the
+            // states from before any of them were written
+            let scratch_buffer = app_data.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Animation Move Buffer"),
+                size: moves.len() as wgpu::BufferAddress * state_size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            for (i, (from, _)) in moves.iter().enumerate() {
+                encoder.copy_buffer_to_buffer(&self.state_buffer, *from as wgpu::BufferAddress * state_size, &scratch_buffer, i as wgpu::BufferAddress * state_size, state_size);
+            }
+            for (i, (_, to)) in moves.iter().enumerate() {
+                encoder.copy_buffer_to_buffer(&scratch_buffer, i as wgpu::BufferAddress * state_size, &self.state_buffer, *to as wgpu::BufferAddress * state_size, state_size);
+            }
+        }
+
+        let new_indices = origins.iter()
+            .filter(|(_, origin)| *origin == InstanceOrigin::New)
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+        if !new_indices.is_empty() {
+            let states = new_indices.iter().map(|index| InstanceState::from(&instances[*index])).collect::<Vec<_>>();
+            let upload_buffer = app_data.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Animation Upload Buffer"),
+                contents: bytemuck::cast_slice(&states),
+                usage: wgpu::BufferUsages::COPY_SRC,
+            });
+            // Neighbouring new instances, e.g. a batch pushed on the end, go over in one copy
+            let mut i = 0;
+            while i < new_indices.len() {
+                let mut run_length = 1;
+                while i + run_length < new_indices.len() && new_indices[i + run_length] == new_indices[i] + run_length {
+                    run_length += 1;
+                }
+                encoder.copy_buffer_to_buffer(
+                    &upload_buffer,
+                    i as wgpu::BufferAddress * state_size,
+                    &self.state_buffer,
+                    new_indices[i] as wgpu::BufferAddress * state_size,
+                    run_length as wgpu::BufferAddress * state_size,
+                );
+                i += run_length;
+            }
+        }
+    }
+}
+
+fn create_buffer<T>(
+    device: &wgpu::Device,
+    label: &str,
+    capacity: usize,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (capacity * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+// Uploads `range` of `instances` as `T`s
+fn write<T: bytemuck::Pod + for<'a> From<&'a Instance>>(
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    instances: &[Instance],
+    range: std::ops::Range<usize>,
+) {
+    if range.is_empty() {
+        return;
+    }
+    let data = instances[range.clone()].iter().map(T::from).collect::<Vec<_>>();
+    queue.write_buffer(buffer, (range.start * std::mem::size_of::<T>()) as wgpu::BufferAddress, bytemuck::cast_slice(&data));
+}
@@ -1,21 +1,31 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 #[cfg(not(target_arch="wasm32"))]
 use std::time::Instant;
 #[cfg(target_arch="wasm32")]
 use web_time::Instant;
-use log::{debug, error, log_enabled, info, Level};
+use log::{debug, error, log_enabled, info, warn, Level};
 use wgpu::TextureFormat;
 
+use crate::camera::Camera;
+use crate::error::{Error, Result};
 use crate::gui::renderer::EguiRenderer;
+use crate::gui::SendAny;
+use crate::gui::windows::adapter::AdapterEvent;
+use crate::gui::windows::present_mode::{PresentModeEvent, SetPresentModeEvent};
+use crate::screenshot;
+use crate::texture::Texture;
+use crate::timestep::UpdateMode;
 
 use egui_wgpu::ScreenDescriptor;
 
 use winit::dpi::PhysicalSize;
-use winit::event::WindowEvent;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::{
     event::Event,
     event_loop::EventLoop,
-    window::WindowBuilder,
+    window::{CursorGrabMode, WindowBuilder},
 };
 
 pub type WindowEventFn<T> = fn(app_data: &mut AppData, state: &mut T, window_event: &WindowEvent);
@@ -23,61 +33,392 @@ pub type ResizeFn<T> = fn(app_data: &mut AppData, state: &mut T, size: (u32, u32
 pub type UpdateFn<T> = fn(app_data: &mut AppData, state: &mut T);
 pub type RenderFn<T> = fn(app_data: &mut AppData, state: &mut T, view: wgpu::TextureView, encoder: wgpu::CommandEncoder);
 
+// Implemented by application-specific state to hook into `App`'s event loop. Every method has a default that does nothing
+// (other than `render`, which submits the encoder), so implementors only need to override what they use
+pub trait Application: 'static {
+    // Called once, before the first frame
+    fn init(
+        &mut self,
+        _app_data: &mut AppData,
+    ) {}
+
+    // Called on every WindowEvent that `App` doesn't handle itself (i.e. other than CloseRequested, Resized, ScaleFactorChanged and its own key bindings)
+    fn window_event(
+        &mut self,
+        _app_data: &mut AppData,
+        _window_event: &WindowEvent,
+    ) {}
+
+    // Called on every DeviceEvent, i.e. raw input that isn't tied to the window (such as unaccelerated mouse motion for mouse look)
+    fn device_event(
+        &mut self,
+        _app_data: &mut AppData,
+        _device_event: &DeviceEvent,
+    ) {}
+
+    // Called on every ScaleFactorChanged and Resized WindowEvent, after the surface has been reconfigured
+    fn resize(
+        &mut self,
+        _app_data: &mut AppData,
+        _size: (u32, u32),
+    ) {}
+
+    // The camera `App` should keep matching the surface's aspect ratio, if any. Its aspect ratio is updated on every resize,
+    // before `resize` is called
+    fn camera(
+        &mut self,
+    ) -> Option<&mut Camera> {
+        None
+    }
+
+    // Called before `render` every frame
+    fn update(
+        &mut self,
+        _app_data: &mut AppData,
+    ) {}
+
+    // Called after `update` every frame. Implementations are responsible for submitting `encoder`
+    fn render(
+        &mut self,
+        app_data: &mut AppData,
+        _view: wgpu::TextureView,
+        encoder: wgpu::CommandEncoder,
+    ) {
+        app_data.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Called with every event raised by a GUI window that `App` doesn't handle itself
+    fn gui_event(
+        &mut self,
+        _app_data: &mut AppData,
+        _event: &SendAny,
+    ) {}
+
+    // Called after the device was lost and `AppData` has created a new one. Every GPU resource the application holds
+    // (passes, models, textures...) belongs to the lost device and has to be recreated here
+    fn reinit(
+        &mut self,
+        _app_data: &mut AppData,
+    ) {}
+
+    // Called once when the event loop is about to exit
+    fn exit(
+        &mut self,
+        _app_data: &mut AppData,
+    ) {}
+}
+
+// Adapts a state struct and a set of callbacks to `Application`, for apps written before the trait existed
+pub struct FnApplication<T: 'static> {
+    state: T, // A struct to hold application-specific state
+
+    window_event_fn: WindowEventFn<T>,
+    resize_fn: ResizeFn<T>,
+    update_fn: UpdateFn<T>,
+    render_fn: RenderFn<T>,
+}
+
+impl<T: 'static> FnApplication<T> {
+    pub fn new(
+        state: T,
+        window_event_fn: WindowEventFn<T>,
+        resize_fn: ResizeFn<T>,
+        update_fn: UpdateFn<T>,
+        render_fn: RenderFn<T>,
+    ) -> Self {
+        FnApplication {
+            state,
+            window_event_fn,
+            resize_fn,
+            update_fn,
+            render_fn,
+        }
+    }
+}
+
+impl<T: 'static> Application for FnApplication<T> {
+    fn window_event(
+        &mut self,
+        app_data: &mut AppData,
+        window_event: &WindowEvent,
+    ) {
+        (self.window_event_fn)(app_data, &mut self.state, window_event);
+    }
+
+    fn resize(
+        &mut self,
+        app_data: &mut AppData,
+        size: (u32, u32),
+    ) {
+        (self.resize_fn)(app_data, &mut self.state, size);
+    }
+
+    fn update(
+        &mut self,
+        app_data: &mut AppData,
+    ) {
+        (self.update_fn)(app_data, &mut self.state);
+    }
+
+    fn render(
+        &mut self,
+        app_data: &mut AppData,
+        view: wgpu::TextureView,
+        encoder: wgpu::CommandEncoder,
+    ) {
+        (self.render_fn)(app_data, &mut self.state, view, encoder);
+    }
+}
+
+// Where frames end up: either a window's `Surface` or a texture owned by `AppData` (for headless rendering)
+pub enum RenderTarget {
+    Surface(wgpu::Surface<'static>),
+    Offscreen(Texture),
+}
+
 pub struct AppData {
     last_frame_time_instant: Instant,
     render_time_instant: Instant,
     update_time_instant: Instant,
 
     pub fps: f64,
-    pub delta_time: f64,
+    pub delta_time: f64, // Seconds since the last frame
     pub render_time: f64,
     pub update_time: f64,
 
+    pub update_mode: UpdateMode,
+    pub update_delta_time: f64, // Seconds the current `update` call should advance the simulation by, equal to `delta_time` unless `update_mode` is `Fixed`
+    pub interpolation_alpha: f64, // How far rendering is between the previous and current update, in [0, 1). Always 1 unless `update_mode` is `Fixed`
+
+    instance: wgpu::Instance, // Kept around to request a new adapter if the device is lost
+    app_config: AppConfig,
+    device_lost: Arc<AtomicBool>, // Set from the device lost callback, see `AppData::recreate_device`
+
+    pub adapter: wgpu::Adapter,
+    pub adapter_info: wgpu::AdapterInfo, // The adapter `AppConfig` settled on, e.g. for display in the GUI
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     
     pub size: winit::dpi::PhysicalSize<u32>,
+    pub scale_factor: f64, // Physical pixels per logical pixel of the window (1.0 when rendering headless)
 
-    pub surface: wgpu::Surface<'static>,
+    pub window: Option<Arc<winit::window::Window>>, // `None` when rendering headless
+    pub target: RenderTarget,
+    pub surface_capabilities: wgpu::SurfaceCapabilities, // Empty when rendering headless
 
-    pub egui_renderer: EguiRenderer,
+    pub egui_renderer: Option<EguiRenderer>, // `None` when rendering headless, as egui needs a window to take input from
 }
 
-impl AppData {
-    pub async fn new(
-        window: Arc<winit::window::Window>,
-    ) -> Self {
-        // The `instance` is a handle to our GPU. Its main purpose is to create `Adapter`s and `Surface`s.
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(), // Backends::all = Vulkan + Metal + DX12 + Browser WebGPU
+// Options for creating the adapter, device and surface. Choices the adapter or surface can't support are replaced with a
+// supported fallback and a logged warning, rather than failing
+#[derive(Clone)]
+pub struct AppConfig {
+    pub backends: wgpu::Backends, // Backends::all = Vulkan + Metal + DX12 + Browser WebGPU
+    pub power_preference: wgpu::PowerPreference, // `LowPower` will pick an adapter that favors battery life, such as an integrated GPU. 
+                                                 // `HighPerformance` will pick an adapter for more power-hungry yet more performant GPUs, such as a dedicated graphics card.
+                                                 // `default` will pick the first available adapter.
+    pub force_fallback_adapter: bool, // Forces the instance to pick an adapter compatible with all hardware (typically forces a "software" rendering backend for instead of using GPU hardware)
+    pub required_features: wgpu::Features, // Enable features not guaranteed to be supported. See docs for full list
+    pub required_limits: wgpu::Limits, // Describes the limits an adapter/device supports. Recommended to start with the most resticted limits and and manually increase to stay running on all hardware that supports the limits needed
+    pub present_mode: Option<wgpu::PresentMode>, // `None` uses the first mode the surface supports. PresentMode::Fifo is guaranteed to be supported on all platforms and is essentially VSync
+    pub desired_maximum_frame_latency: u32, // How many frames the presentation engine may queue up. Lower values reduce latency at the cost of throughput
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            present_mode: None,
+            desired_maximum_frame_latency: 2,
+        }
+    }
+}
+
+impl AppConfig {
+    // Software adapter with limits that software adapters are known to support, used by `AppData::new_headless`
+    pub fn headless() -> Self {
+        Self {
+            force_fallback_adapter: true,
+            required_limits: wgpu::Limits::downlevel_defaults(),
             ..Default::default()
-        });
+        }
+    }
 
-        // The `surface` is the part of the window that we are drawing to.
-        let surface = instance.create_surface(Arc::clone(&window)).unwrap();
-
-        // The `adapter` is a handle for our actual graphics card. We need it to create the `Device` and `Queue`.
-        // `Adapter`s are locked to a specific backend (i.e., if you have two GPUs on windows you'll have 4 `Adapters` to chose from: 2 Vulkan and 2 DirectX).
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(), // `LowPower` will pick an adapter that favors battery life, such as an integrated GPU. 
-                                                                    // `HighPerformance` will pick an adapter for more power-hungry yet more performant GPUs, such as a dedicated graphics card.
-                                                                    // `default` will pick the first available adapter.
-                compatible_surface: Some(&surface), // Find an adapter compatible with the supplied surface.
-                force_fallback_adapter: false, // Forces the instance to pick an adapter compatible with all hardware (typically forces a "software" rendering backend for instead of using GPU hardware)
-            },
-        ).await.unwrap();
+    pub fn with_backends(
+        mut self,
+        backends: wgpu::Backends,
+    ) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(
+        mut self,
+        power_preference: wgpu::PowerPreference,
+    ) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_force_fallback_adapter(
+        mut self,
+        force_fallback_adapter: bool,
+    ) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    pub fn with_required_features(
+        mut self,
+        required_features: wgpu::Features,
+    ) -> Self {
+        self.required_features = required_features;
+        self
+    }
+
+    pub fn with_required_limits(
+        mut self,
+        required_limits: wgpu::Limits,
+    ) -> Self {
+        self.required_limits = required_limits;
+        self
+    }
+
+    pub fn with_present_mode(
+        mut self,
+        present_mode: wgpu::PresentMode,
+    ) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    pub fn with_desired_maximum_frame_latency(
+        mut self,
+        desired_maximum_frame_latency: u32,
+    ) -> Self {
+        self.desired_maximum_frame_latency = desired_maximum_frame_latency;
+        self
+    }
+
+    // Finds an adapter matching this config, relaxing the backend and fallback adapter choices if none can be found.
+    // A surface for `window` (if any) is created alongside, as it has to come from the same `Instance` as the adapter.
+    async fn request_adapter(
+        &self,
+        window: Option<&Arc<winit::window::Window>>,
+    ) -> Result<(wgpu::Instance, wgpu::Adapter, Option<wgpu::Surface<'static>>)> {
+        let mut attempts = vec![(self.backends, self.force_fallback_adapter)];
+        if self.backends != wgpu::Backends::all() {
+            attempts.push((wgpu::Backends::all(), self.force_fallback_adapter));
+        }
+        if self.force_fallback_adapter {
+            attempts.push((wgpu::Backends::all(), false));
+        }
+
+        for (backends, force_fallback_adapter) in attempts {
+            // The `instance` is a handle to our GPU. Its main purpose is to create `Adapter`s and `Surface`s.
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                ..Default::default()
+            });
+
+            // The `surface` is the part of the window that we are drawing to.
+            let surface = window.map(|window| instance.create_surface(Arc::clone(window))).transpose()?;
+
+            // The `adapter` is a handle for our actual graphics card. We need it to create the `Device` and `Queue`.
+            // `Adapter`s are locked to a specific backend (i.e., if you have two GPUs on windows you'll have 4 `Adapters` to chose from: 2 Vulkan and 2 DirectX).
+            let adapter = instance.request_adapter(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    compatible_surface: surface.as_ref(), // Find an adapter compatible with the supplied surface.
+                    force_fallback_adapter,
+                },
+            ).await;
+
+            match adapter {
+                Some(adapter) => {
+                    if (backends, force_fallback_adapter) != (self.backends, self.force_fallback_adapter) {
+                        warn!(
+                            "No adapter found for backends {:?} (force_fallback_adapter: {}), using {:?} (force_fallback_adapter: {}) instead",
+                            self.backends, self.force_fallback_adapter, backends, force_fallback_adapter,
+                        );
+                    }
+                    return Ok((instance, adapter, surface));
+                }
+                None => continue,
+            }
+        }
+        Err(Error::AdapterNotFound)
+    }
+
+    async fn request_device(
+        &self,
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue)> {
+        let mut required_features = self.required_features;
+        let unsupported_features = required_features - adapter.features();
+        if !unsupported_features.is_empty() {
+            warn!("Adapter doesn't support features {:?}, disabling them", unsupported_features);
+            required_features &= adapter.features();
+        }
+
+        let mut required_limits = self.required_limits.clone();
+        if !required_limits.check_limits(&adapter.limits()) {
+            warn!("Adapter doesn't support the required limits, using the adapter's limits instead");
+            required_limits = adapter.limits();
+        }
 
         // The `device` is responsible for the creation of most rendering and compute resources. These are used in commands passed to the `queue`.
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(), // Enable features not guaranteed to be supported. See docs for full list
-                required_limits: wgpu::Limits::default(), // Describes the limits an adapter/device supports. Recommended to start with the most resticted limits and and manually increase to stay running on all hardware that supports the limits needed
+                required_features,
+                required_limits,
                 label: None,
             },
             None,
-        ).await.unwrap();
+        ).await?;
+        Ok((device, queue))
+    }
+
+    fn choose_present_mode(
+        &self,
+        surface_capabilities: &wgpu::SurfaceCapabilities,
+    ) -> wgpu::PresentMode {
+        match self.present_mode {
+            Some(present_mode) if surface_capabilities.present_modes.contains(&present_mode) => present_mode,
+            Some(present_mode) => {
+                warn!(
+                    "Surface doesn't support present mode {:?}, using {:?} instead (supported: {:?})",
+                    present_mode, surface_capabilities.present_modes[0], surface_capabilities.present_modes,
+                );
+                surface_capabilities.present_modes[0]
+            }
+            None => surface_capabilities.present_modes[0],
+        }
+    }
+}
+
+impl AppData {
+    pub async fn new(
+        window: Arc<winit::window::Window>,
+    ) -> Result<Self> {
+        Self::new_with_config(window, &AppConfig::default()).await
+    }
+
+    pub async fn new_with_config(
+        window: Arc<winit::window::Window>,
+        app_config: &AppConfig,
+    ) -> Result<Self> {
+        let (instance, adapter, surface) = app_config.request_adapter(Some(&window)).await?;
+        let surface = surface.expect("a surface is always created when a window is given");
+        let adapter_info = adapter.get_info();
+        info!("adapter {:?}", adapter_info);
+
+        let (device, queue) = app_config.request_device(&adapter).await?;
 
         // Size is only valid once we enter the render loop
         let mut size: PhysicalSize<u32> = window.inner_size();
@@ -91,16 +432,19 @@ impl AppData {
         let surface_format = surface_capabilities.formats.iter()
             .find(|f| **f == TextureFormat::Bgra8UnormSrgb)
             .unwrap_or(&surface_capabilities.formats[0]);
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if surface_capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            usage |= wgpu::TextureUsages::COPY_SRC; // Lets us read frames back for screenshots
+        }
         let mut config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: *surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_capabilities.present_modes[0], // Play with this or enable runtime selection. PresentMode::Fifo is guaranteed to be supported on all platforms and is essentially VSync
-                                                                 // For runtime selection, `let modes = &surface_caps.present_modes;` will get a list of all `PresentMode`s supported by the surface
+            present_mode: app_config.choose_present_mode(&surface_capabilities),
             alpha_mode: surface_capabilities.alpha_modes[0],
             view_formats: vec![],
-            desired_maximum_frame_latency: 2, // TODO: Look into this
+            desired_maximum_frame_latency: app_config.desired_maximum_frame_latency,
         };
         let view_format = config.format.add_srgb_suffix();
         config.view_formats.push(view_format);
@@ -120,7 +464,7 @@ impl AppData {
             screen_descriptor,
         );
 
-        AppData {
+        Ok(AppData {
             last_frame_time_instant: Instant::now(),
             render_time_instant: Instant::now(),
             update_time_instant: Instant::now(),
@@ -128,42 +472,285 @@ impl AppData {
             delta_time: 1.0,
             render_time: 1.0,
             update_time: 1.0,
+            update_mode: UpdateMode::default(),
+            update_delta_time: 1.0,
+            interpolation_alpha: 1.0,
+            instance,
+            app_config: app_config.clone(),
+            device_lost: watch_device_lost(&device),
+            adapter,
+            adapter_info,
             device,
             queue,
             config,
             size,
-            surface,
-            egui_renderer,
+            scale_factor: window.scale_factor(),
+            window: Some(Arc::clone(&window)),
+            target: RenderTarget::Surface(surface),
+            surface_capabilities,
+            egui_renderer: Some(egui_renderer),
+        })
+    }
+
+    // Creates an `AppData` that renders into an owned offscreen texture of the given size and format instead of a window.
+    // Uses the fallback (software) adapter, so passes can be driven on machines without a GPU or a display.
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self> {
+        Self::new_headless_with_config(width, height, format, &AppConfig::headless()).await
+    }
+
+    pub async fn new_headless_with_config(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        app_config: &AppConfig,
+    ) -> Result<Self> {
+        let (instance, adapter, _) = app_config.request_adapter(None).await?;
+        let adapter_info = adapter.get_info();
+        info!("headless adapter {:?}", adapter_info);
+
+        let (device, queue) = app_config.request_device(&adapter).await?;
+
+        let size = PhysicalSize::new(width.max(1), height.max(1));
+
+        // There's no surface to configure, but passes read the target's format and size from `config` so we fill it in anyway
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![format],
+            desired_maximum_frame_latency: app_config.desired_maximum_frame_latency,
+        };
+        let target = Texture::create_render_target(&device, &config, "Offscreen Render Target");
+
+        Ok(AppData {
+            last_frame_time_instant: Instant::now(),
+            render_time_instant: Instant::now(),
+            update_time_instant: Instant::now(),
+            fps: 1.0,
+            delta_time: 1.0,
+            render_time: 1.0,
+            update_time: 1.0,
+            update_mode: UpdateMode::default(),
+            update_delta_time: 1.0,
+            interpolation_alpha: 1.0,
+            instance,
+            app_config: app_config.clone(),
+            device_lost: watch_device_lost(&device),
+            adapter,
+            adapter_info,
+            device,
+            queue,
+            config,
+            size,
+            scale_factor: 1.0,
+            window: None,
+            target: RenderTarget::Offscreen(target),
+            surface_capabilities: wgpu::SurfaceCapabilities::default(),
+            egui_renderer: None,
+        })
+    }
+
+    // Hides the cursor and stops it from leaving the window (e.g. for mouse look), or releases it again
+    pub fn set_cursor_grab(
+        &self,
+        grab: bool,
+    ) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        let result = if grab {
+            // Not every platform supports locking the cursor in place, confining it to the window is the next best thing
+            window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(e) = result {
+            warn!("Couldn't set cursor grab: {}", e);
+        }
+        window.set_cursor_visible(!grab);
+    }
+
+    pub fn is_device_lost(
+        &self,
+    ) -> bool {
+        self.device_lost.load(Ordering::SeqCst)
+    }
+
+    // Fault injection for testing recovery: destroys the device, which wgpu reports through the same callback as a real device loss
+    pub fn simulate_device_lost(
+        &self,
+    ) {
+        warn!("Simulating device loss");
+        self.device.destroy();
+        self.device.poll(wgpu::Maintain::Wait); // The lost callback is only called once the device has been polled
+    }
+
+    // Replaces a lost device with a new one from a newly requested adapter, then reconfigures the render target and egui.
+    // Any other GPU resources created from the old device are now invalid, so `App` follows this with `Application::reinit`
+    pub async fn recreate_device(
+        &mut self,
+    ) -> Result<()> {
+        let compatible_surface = match &self.target {
+            RenderTarget::Surface(surface) => Some(surface),
+            RenderTarget::Offscreen(_) => None,
+        };
+        let mut adapter = None;
+        for force_fallback_adapter in [self.app_config.force_fallback_adapter, false] {
+            adapter = self.instance.request_adapter(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: self.app_config.power_preference,
+                    compatible_surface,
+                    force_fallback_adapter,
+                },
+            ).await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.ok_or(Error::AdapterNotFound)?;
+        let adapter_info = adapter.get_info();
+        info!("recreating device on adapter {:?}", adapter_info);
+
+        let (device, queue) = self.app_config.request_device(&adapter).await?;
+        self.device_lost = watch_device_lost(&device);
+        self.device = device;
+        self.queue = queue;
+
+        if let RenderTarget::Surface(surface) = &self.target {
+            self.surface_capabilities = surface.get_capabilities(&adapter);
+            if !self.surface_capabilities.present_modes.contains(&self.config.present_mode) {
+                self.config.present_mode = self.surface_capabilities.present_modes[0];
+            }
         }
+        self.adapter = adapter;
+        self.adapter_info = adapter_info;
+        self.configure_target();
+
+        if let Some(egui_renderer) = &mut self.egui_renderer {
+            egui_renderer.recreate(&self.device, self.config.view_formats[0]);
+            // The fallback may have landed on a different adapter to the one that was lost
+            egui_renderer.broadcast_event(&AdapterEvent { adapter_info: self.adapter_info.clone() });
+        }
+        self.broadcast_present_mode();
+
+        Ok(())
+    }
+
+    // Applies the current `config` to the render target, reconfiguring the surface or recreating the offscreen texture
+    pub fn configure_target(
+        &mut self,
+    ) {
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = Texture::create_render_target(&self.device, &self.config, "Offscreen Render Target");
+            }
+        }
+    }
+
+    // Switches to `present_mode` if the surface supports it, returning whether it does
+    pub fn set_present_mode(
+        &mut self,
+        present_mode: wgpu::PresentMode,
+    ) -> bool {
+        if !self.surface_capabilities.present_modes.contains(&present_mode) {
+            warn!("Surface doesn't support present mode {:?} (supported: {:?})", present_mode, self.surface_capabilities.present_modes);
+            return false;
+        }
+        info!("Switching present mode to {:?}", present_mode);
+        self.config.present_mode = present_mode;
+        self.configure_target();
+        self.broadcast_present_mode();
+        true
+    }
+
+    // Switches to the next present mode the surface supports, wrapping around
+    pub fn cycle_present_mode(
+        &mut self,
+    ) {
+        let present_modes = &self.surface_capabilities.present_modes;
+        let Some(current) = present_modes.iter().position(|mode| *mode == self.config.present_mode) else {
+            return;
+        };
+        let next = present_modes[(current + 1) % present_modes.len()];
+        self.set_present_mode(next);
+    }
+
+    // Lets GUI windows (e.g. `PresentModeWindow`) know the current and available present modes
+    fn broadcast_present_mode(
+        &mut self,
+    ) {
+        if let Some(egui_renderer) = &mut self.egui_renderer {
+            egui_renderer.broadcast_event(&PresentModeEvent {
+                present_mode: self.config.present_mode,
+                available_present_modes: self.surface_capabilities.present_modes.clone(),
+            });
+        }
+    }
+
+    // Reads the offscreen target back to the CPU. Window surface frames only exist while `App` is rendering, so use `App`'s screenshot key for those
+    pub fn read_target(
+        &self,
+    ) -> Result<image::RgbaImage> {
+        match &self.target {
+            RenderTarget::Offscreen(texture) => screenshot::read_texture(&self.device, &self.queue, &texture.texture),
+            RenderTarget::Surface(_) => Err(Error::Readback("surface frames can only be read back inside App::render")),
+        }
+    }
+
+    pub fn save_screenshot(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<()> {
+        screenshot::save_png(&self.read_target()?, path)
     }
 }
 
-pub struct App<T: 'static> {
-    state: T, // A struct to hold application-specific state
+fn watch_device_lost(
+    device: &wgpu::Device,
+) -> Arc<AtomicBool> {
+    let device_lost = Arc::new(AtomicBool::new(false));
+    let device_lost_ref = Arc::clone(&device_lost);
+    device.set_device_lost_callback(move |reason, message| {
+        match reason {
+            // Dropping the device (e.g. when replacing it after it was lost) also calls the callback, but isn't a loss
+            wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback => {}
+            _ => {
+                error!("Device lost ({:?}): {}", reason, message);
+                device_lost_ref.store(true, Ordering::SeqCst);
+            }
+        }
+    });
+    device_lost
+}
+
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+pub const PRESENT_MODE_KEY: KeyCode = KeyCode::F10; // Cycles through the surface's supported present modes
+
+pub struct App<A: Application> {
+    application: A, // Application-specific state and callbacks
     app_data: AppData, // Holds generic application state
-    
-    window_event_fn: WindowEventFn<T>, // Called on every WindowEvent other than CloseRequested and Resized
-    resize_fn: ResizeFn<T>, // Called on every ScaleFactorChaanged and Resized WindowEvents
-    update_fn: UpdateFn<T>, // Called before RenderFn every frame
-    render_fn: RenderFn<T>, // Called after UpdateFn every frame
+
+    screenshot_requested: bool, // Set by pressing `SCREENSHOT_KEY`, the next frame is saved as a PNG before it is presented
 }
 
-impl<T: 'static> App<T> {
+impl<A: Application> App<A> {
     pub async fn new(
-        state: T,
+        application: A,
         app_data: AppData,
-        window_event_fn: WindowEventFn<T>,
-        resize_fn: ResizeFn<T>,
-        update_fn: UpdateFn<T>,
-        render_fn: RenderFn<T>,
     ) -> Self {
         App {
-            state,
+            application,
             app_data,
-            window_event_fn,
-            resize_fn,
-            update_fn,
-            render_fn,
+            screenshot_requested: false,
         }
     }
 
@@ -172,44 +759,182 @@ impl<T: 'static> App<T> {
         new_size: winit::dpi::PhysicalSize<u32>
     ) {
         // Configures `self.surface` to match `new_size`.
-        if new_size.width <= 0 && new_size.height <= 0 { // height or width being 0 may cause crashes
+        if new_size.width == 0 || new_size.height == 0 { // height or width being 0 may cause crashes
             return;
         }
 
         self.app_data.size = new_size;
         self.app_data.config.width = new_size.width;
         self.app_data.config.height = new_size.height;
-        self.app_data.surface.configure(&self.app_data.device, &self.app_data.config);
-        (self.resize_fn)(&mut self.app_data, &mut self.state, new_size.into());
+        self.app_data.configure_target();
+        if let Some(egui_renderer) = &mut self.app_data.egui_renderer {
+            egui_renderer.screen_descriptor = ScreenDescriptor {
+                size_in_pixels: [self.app_data.config.width, self.app_data.config.height],
+                pixels_per_point: self.app_data.scale_factor as f32,
+            };
+        }
+        if let Some(camera) = self.application.camera() {
+            camera.resize(new_size.width, new_size.height);
+        }
+        self.application.resize(&mut self.app_data, new_size.into());
+    }
+
+    fn update(
+        &mut self,
+    ) {
+        match &mut self.app_data.update_mode {
+            UpdateMode::Variable => {
+                self.app_data.update_delta_time = self.app_data.delta_time;
+                self.app_data.interpolation_alpha = 1.0;
+                self.application.update(&mut self.app_data);
+            }
+            UpdateMode::Fixed(timestep) => {
+                let steps = timestep.accumulate(self.app_data.delta_time);
+                self.app_data.update_delta_time = timestep.step();
+                self.app_data.interpolation_alpha = timestep.alpha();
+                for _ in 0..steps {
+                    self.application.update(&mut self.app_data);
+                }
+            }
+        }
+    }
+
+    // Recovers the device if it was lost, then updates and renders a frame. Returns whether the app can carry on.
+    // Called for every redraw the window asks for, or call it directly to drive an `App` over a headless `AppData`
+    pub fn redraw(
+        &mut self,
+    ) -> bool {
+        if self.app_data.is_device_lost() && !self.recover_device() {
+            return false;
+        }
+
+        self.app_data.delta_time = self.app_data.last_frame_time_instant.elapsed().as_secs_f64();
+        self.app_data.fps = 1.0 / self.app_data.delta_time;
+        self.app_data.last_frame_time_instant = Instant::now();
+
+        self.app_data.update_time_instant = Instant::now();
+        self.update();
+        self.app_data.update_time = self.app_data.update_time_instant.elapsed().as_secs_f64();
+
+        self.app_data.render_time_instant = Instant::now();
+        match self.render() {
+            Ok(_) => {}
+            // The surface is lost, so we need to reconfigure the surface
+            Err(Error::Surface(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => self.resize(self.app_data.size),
+            // The system is OOM, so let's just quit
+            Err(Error::Surface(wgpu::SurfaceError::OutOfMemory)) => return false,
+            // Anything else should be resolved by the next frame, so print an error and move on
+            Err(e) => eprintln!("{}", e),
+        }
+        self.app_data.render_time = self.app_data.render_time_instant.elapsed().as_secs_f64();
+        true
     }
 
     fn render(
         &mut self,
-    ) -> Result<(), wgpu::SurfaceError> {
-        // Get a frame to render to
-        let output = self.app_data.surface.get_current_texture()?;
-        let view: wgpu::TextureView = output.texture.create_view(&wgpu::TextureViewDescriptor {
+    ) -> Result<()> {
+        // Get a frame to render to. An offscreen target is always there, so has no frame to present
+        let view_descriptor = wgpu::TextureViewDescriptor {
             format: Some(self.app_data.config.view_formats[0]),
             ..wgpu::TextureViewDescriptor::default()
-        });
+        };
+        let (output, view) = match &self.app_data.target {
+            RenderTarget::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&view_descriptor);
+                (Some(output), view)
+            }
+            RenderTarget::Offscreen(target) => (None, target.texture.create_view(&view_descriptor)),
+        };
         // Create a `CommandEncoder` to create the store commands in a command buffer that will be sent to the GPU
         let encoder = self.app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
-        (self.render_fn)(&mut self.app_data, &mut self.state, view, encoder);
+        self.application.render(&mut self.app_data, view, encoder);
 
-        output.present();
+        if let Some(output) = output {
+            if self.screenshot_requested {
+                self.screenshot_requested = false;
+                self.save_screenshot(&output.texture);
+            }
+            output.present();
+        }
+
+        // Reconfiguring the surface is only safe once the frame has been presented
+        self.handle_gui_events();
 
         Ok(())
     }
 
+    // Returns whether the app can carry on
+    fn recover_device(
+        &mut self,
+    ) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                error!("Device lost, recovering isn't supported on the web");
+                false
+            } else {
+                match pollster::block_on(self.app_data.recreate_device()) {
+                    Ok(()) => {
+                        self.application.reinit(&mut self.app_data);
+                        true
+                    }
+                    Err(e) => {
+                        error!("Couldn't recover from device loss: {}", e);
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_gui_events(
+        &mut self,
+    ) {
+        let Some(egui_renderer) = &mut self.app_data.egui_renderer else {
+            return;
+        };
+        for event in egui_renderer.take_events() {
+            if let Some(set_present_mode_event) = event.downcast_ref::<SetPresentModeEvent>() {
+                self.app_data.set_present_mode(set_present_mode_event.present_mode);
+            } else {
+                self.application.gui_event(&mut self.app_data, event.as_ref());
+            }
+        }
+    }
+
+    fn save_screenshot(
+        &self,
+        texture: &wgpu::Texture,
+    ) {
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            error!("Can't take a screenshot, the surface doesn't support being copied from");
+            return;
+        }
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                error!("Screenshots aren't supported on the web");
+            } else {
+                let path = screenshot::default_path();
+                match screenshot::read_texture(&self.app_data.device, &self.app_data.queue, texture)
+                    .and_then(|image| screenshot::save_png(&image, &path)) {
+                    Ok(_) => info!("Saved screenshot to {}", path),
+                    Err(e) => error!("Failed to save screenshot: {}", e),
+                }
+            }
+        }
+    }
+
     pub fn run(
         mut self,
         window: Arc<winit::window::Window>,
         event_loop: EventLoop<()>,
     ) {
         window.set_visible(true);
+        self.application.init(&mut self.app_data);
+        self.app_data.broadcast_present_mode(); // GUI windows are usually added in `init`, so they've missed the initial present mode
 
         let _ = event_loop.run(move |event, elwt| match event {
             Event::WindowEvent {
@@ -226,45 +951,80 @@ impl<T: 'static> App<T> {
                     WindowEvent::Resized(physical_size) => {
                         log::info!("Resized to {:?}", physical_size);
                         self.resize(*physical_size);
-                        self.app_data.egui_renderer.screen_descriptor = ScreenDescriptor {
-                            size_in_pixels: [self.app_data.config.width, self.app_data.config.height],
-                            pixels_per_point: window.scale_factor() as f32,
-                        };
                     }
-                    // TODO: Handle ScaleFactorChanged
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        log::info!("Scale factor changed to {}", scale_factor);
+                        // egui tracks the scale factor itself for its input handling
+                        if let Some(egui_renderer) = &mut self.app_data.egui_renderer {
+                            egui_renderer.handle_input(event);
+                        }
+                        // Keep the window's logical size. If the windowing system picks a different size it will follow up with a `Resized`
+                        let new_size = scaled_physical_size(self.app_data.size, self.app_data.scale_factor, *scale_factor);
+                        self.app_data.scale_factor = *scale_factor;
+                        self.resize(new_size);
+                    }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(SCREENSHOT_KEY),
+                            state: ElementState::Pressed,
+                            repeat: false,
+                            ..
+                        },
+                        ..
+                    } => {
+                        self.screenshot_requested = true;
+                    }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(PRESENT_MODE_KEY),
+                            state: ElementState::Pressed,
+                            repeat: false,
+                            ..
+                        },
+                        ..
+                    } => {
+                        self.app_data.cycle_present_mode();
+                    }
                     WindowEvent::RedrawRequested => {
-                        self.app_data.delta_time = self.app_data.last_frame_time_instant.elapsed().as_secs_f64();
-                        self.app_data.fps = 1.0 / self.app_data.delta_time;
-                        self.app_data.last_frame_time_instant = Instant::now();
-                        
-                        self.app_data.update_time_instant = Instant::now();
-                        (self.update_fn)(&mut self.app_data, &mut self.state);
-                        self.app_data.update_time = self.app_data.update_time_instant.elapsed().as_secs_f64();
-                        
-                        self.app_data.render_time_instant = Instant::now();
-                        match self.render() {
-                            Ok(_) => {}
-                            // The surface is lost, so we need to reconfigure the surface
-                            Err(wgpu::SurfaceError::Lost) => self.resize(self.app_data.size),
-                            // The system is OOM, so let's just quit
-                            Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
-                            // Anything else should be resolved by the next frame, so print an error and move on
-                            Err(e) => eprintln!("{:?}", e),
+                        if !self.redraw() {
+                            elwt.exit();
+                            return;
                         }
-                        self.app_data.render_time = self.app_data.render_time_instant.elapsed().as_secs_f64();
                         window.request_redraw();
                     }
                     _ => {
-                        self.app_data.egui_renderer.handle_input(event);
-                        (self.window_event_fn)(&mut self.app_data, &mut self.state, event)
+                        if let Some(egui_renderer) = &mut self.app_data.egui_renderer {
+                            egui_renderer.handle_input(event);
+                        }
+                        self.application.window_event(&mut self.app_data, event)
                     } ,
                 };
             }
+            Event::DeviceEvent {
+                ref event,
+                ..
+            } => {
+                self.application.device_event(&mut self.app_data, event);
+            }
+            Event::LoopExiting => {
+                self.application.exit(&mut self.app_data);
+            }
             _ => {}
         });
     }
 }
 
+// The physical size a window of `size` physical pixels should have after its scale factor changes, so that its logical size stays the same
+pub fn scaled_physical_size(
+    size: PhysicalSize<u32>,
+    old_scale_factor: f64,
+    new_scale_factor: f64,
+) -> PhysicalSize<u32> {
+    let logical_size = size.to_logical::<f64>(old_scale_factor);
+    let new_size = logical_size.to_physical::<u32>(new_scale_factor);
+    PhysicalSize::new(new_size.width.max(1), new_size.height.max(1)) // height or width being 0 may cause crashes
+}
+
 pub fn create_window(
     title: &str,
     event_loop: &EventLoop<()>,
@@ -298,19 +1058,19 @@ pub async fn run_app<T: 'static>(
     resize_fn: ResizeFn<T>,
     update_fn: UpdateFn<T>,
     render_fn: RenderFn<T>,
-){
+) {
     let event_loop = EventLoop::new().unwrap();
     let window = Arc::new(create_window(title, &event_loop));
 
-    let app_data = AppData::new(Arc::clone(&window)).await;
+    let app_data = AppData::new(Arc::clone(&window)).await.unwrap();
 
-    let app = App::new(
+    let application = FnApplication::new(
         state,
-        app_data,
         window_event_fn,
         resize_fn,
         update_fn,
         render_fn,
-    ).await;
+    );
+    let app = App::new(application, app_data).await;
     app.run(window, event_loop);
 }
\ No newline at end of file
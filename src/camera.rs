@@ -1,6 +1,10 @@
-use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::dpi::PhysicalPosition;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::keyboard::{PhysicalKey, KeyCode};
 use cgmath;
+use serde::{Deserialize, Serialize};
+
+use crate::{culling::Frustum, picking::Ray};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -26,16 +30,59 @@ impl CameraUniform {
         self.view_position = camera.eye.to_homogeneous().into();
         self.view_proj = camera.build_view_projection_matrix().into();
     }
+
+    // The frustum of the camera this was last updated with
+    pub fn frustum(
+        &self,
+    ) -> Frustum {
+        Frustum::from_matrix(&self.view_proj.into())
+    }
 }
 
 
+// Maps OpenGL's [-1, 1] depth range to wgpu's [0, 1]. `Matrix4::new` takes columns, so z' = 0.5 * z + 0.5 * w
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
+// How a `Camera` maps the view onto the screen. The passes need to know, as reversed-Z flips which depths are nearer
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective, // Uses `fovy`, `znear` and `zfar`
+    Orthographic {
+        size: f32, // Height of the view volume in world units, the width follows from the aspect ratio
+    }, // Uses `znear` and `zfar`, parallel lines stay parallel so distances can be compared anywhere on screen
+    // Uses `fovy` and `znear`, with no far plane. Depth is reversed (1 at the near plane, 0 at infinity), which spreads
+    // floating point precision much more evenly over the scene
+    InfinitePerspective,
+}
+
+impl Projection {
+    pub fn is_reversed_z(
+        &self,
+    ) -> bool {
+        matches!(self, Projection::InfinitePerspective)
+    }
+
+    // For `wgpu::DepthStencilState::depth_compare`
+    pub fn depth_compare(
+        &self,
+    ) -> wgpu::CompareFunction {
+        if self.is_reversed_z() { wgpu::CompareFunction::Greater } else { wgpu::CompareFunction::Less }
+    }
+
+    // What to clear the depth texture to, i.e. the depth of something infinitely far away
+    pub fn depth_clear_value(
+        &self,
+    ) -> f32 {
+        if self.is_reversed_z() { 0.0 } else { 1.0 }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Camera {
     eye: cgmath::Point3<f32>,
     target: cgmath::Point3<f32>,
@@ -44,6 +91,7 @@ pub struct Camera {
     fovy: f32,
     znear: f32,
     zfar: f32,
+    projection: Projection,
 }
 
 impl Camera {
@@ -64,42 +112,443 @@ impl Camera {
             fovy,
             znear,
             zfar,
+            projection: Projection::Perspective,
         }
     }
 
+    pub fn with_projection(
+        mut self,
+        projection: Projection,
+    ) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn projection(
+        &self,
+    ) -> Projection {
+        self.projection
+    }
+
+    // Render passes built with the old projection need `update_projection` calling if this changes `is_reversed_z`
+    pub fn set_projection(
+        &mut self,
+        projection: Projection,
+    ) {
+        self.projection = projection;
+    }
+
+    pub fn eye(
+        &self,
+    ) -> cgmath::Point3<f32> {
+        self.eye
+    }
+
+    pub fn set_eye(
+        &mut self,
+        eye: cgmath::Point3<f32>,
+    ) {
+        self.eye = eye;
+    }
+
+    pub fn target(
+        &self,
+    ) -> cgmath::Point3<f32> {
+        self.target
+    }
+
+    pub fn set_target(
+        &mut self,
+        target: cgmath::Point3<f32>,
+    ) {
+        self.target = target;
+    }
+
+    pub fn up(
+        &self,
+    ) -> cgmath::Vector3<f32> {
+        self.up
+    }
+
+    pub fn set_up(
+        &mut self,
+        up: cgmath::Vector3<f32>,
+    ) {
+        self.up = up;
+    }
+
+    pub fn aspect(
+        &self,
+    ) -> f32 {
+        self.aspect
+    }
+
+    pub fn set_aspect(
+        &mut self,
+        aspect: f32,
+    ) {
+        self.aspect = aspect;
+    }
+
+    // Matches the aspect ratio to a new surface size. Zero sizes (e.g. a minimised window) are ignored, as they have no aspect ratio
+    pub fn resize(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.aspect = width as f32 / height as f32;
+    }
+
+    // Vertical field of view in degrees, not used by orthographic projections
+    pub fn fovy(
+        &self,
+    ) -> f32 {
+        self.fovy
+    }
+
+    pub fn set_fovy(
+        &mut self,
+        fovy: f32,
+    ) {
+        self.fovy = fovy;
+    }
+
+    pub fn znear(
+        &self,
+    ) -> f32 {
+        self.znear
+    }
+
+    pub fn set_znear(
+        &mut self,
+        znear: f32,
+    ) {
+        self.znear = znear;
+    }
+
+    // Not used by `Projection::InfinitePerspective`
+    pub fn zfar(
+        &self,
+    ) -> f32 {
+        self.zfar
+    }
+
+    pub fn set_zfar(
+        &mut self,
+        zfar: f32,
+    ) {
+        self.zfar = zfar;
+    }
+
+    pub fn frustum(
+        &self,
+    ) -> Frustum {
+        Frustum::from_matrix(&self.build_view_projection_matrix())
+    }
+
+    // The ray from the camera through pixel (`x`, `y`) of a `width` x `height` surface, e.g. for picking what's under the cursor.
+    // Starts on the near plane. `None` if the view-projection matrix can't be inverted (e.g. the eye is on the target)
+    pub fn screen_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: u32,
+        height: u32,
+    ) -> Option<Ray> {
+        use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix};
+        let inverse_view_proj = self.build_view_projection_matrix().invert()?;
+        // Pixel coordinates run down from the top left, clip space runs up from the bottom left
+        let ndc_x = 2.0 * x / width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height as f32;
+        let unproject = |depth: f32| {
+            let point = inverse_view_proj * cgmath::Vector4::new(ndc_x, ndc_y, depth, 1.0);
+            cgmath::Point3::from_vec(point.truncate() / point.w)
+        };
+        // Depth 0.5 is always at a finite distance, unlike the far end of an infinite projection
+        let near_depth = if self.projection.is_reversed_z() { 1.0 } else { 0.0 };
+        let near = unproject(near_depth);
+        let direction = unproject(0.5) - near;
+        (direction.magnitude2() > 0.0).then(|| Ray::new(near, direction))
+    }
+
     pub fn build_view_projection_matrix(
         &self,
     ) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up); // move the world to be at the position and rotation of the camera
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        OPENGL_TO_WGPU_MATRIX * proj * view // cgmath is built for OpenGL, better (and might be fun) to implement this fn manually
+        self.build_projection_matrix() * view
+    }
+
+    // Maps view space to wgpu's clip space
+    pub fn build_projection_matrix(
+        &self,
+    ) -> cgmath::Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective => {
+                let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+                OPENGL_TO_WGPU_MATRIX * proj // cgmath is built for OpenGL, better (and might be fun) to implement this fn manually
+            }
+            Projection::Orthographic { size } => {
+                let half_height = size / 2.0;
+                let half_width = half_height * self.aspect;
+                let proj = cgmath::ortho(-half_width, half_width, -half_height, half_height, self.znear, self.zfar);
+                OPENGL_TO_WGPU_MATRIX * proj
+            }
+            Projection::InfinitePerspective => {
+                // Built directly in wgpu's clip space, as cgmath has no infinite or reversed-Z projections.
+                // Clip space z is always `znear` and w is the distance in front of the camera, so depth = znear / distance
+                let f = 1.0 / (cgmath::Rad::from(cgmath::Deg(self.fovy)).0 / 2.0).tan();
+                cgmath::Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, self.znear, 0.0,
+                )
+            }
+        }
+    }
+}
+
+// An orbit (arcball-style) controller: dragging with the left mouse button rotates the camera around its target, dragging with
+// the middle mouse button pans the target, and scrolling zooms in and out. With inertia, rotation keeps going after a drag is released
+pub struct OrbitCameraController {
+    pub rotate_sensitivity: f32, // Radians per pixel dragged
+    pub pan_sensitivity: f32, // Fraction of the distance to the target moved per pixel dragged, so panning feels the same at any zoom level
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub inertia: f32, // Seconds for the rotation speed to halve after a drag is released, 0 to stop immediately
+    is_rotating: bool,
+    is_panning: bool,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    rotate_delta: (f32, f32), // Pixels dragged since the last `update_camera`
+    pan_delta: (f32, f32),
+    scroll_delta: f32, // Scroll wheel notches since the last `update_camera`
+    velocity: (f32, f32), // Yaw and pitch in radians per second, carried on by inertia after a drag
+}
+
+impl OrbitCameraController {
+    const SAFE_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.001; // Looking straight up or down makes `look_at_rh` degenerate
+    const SCROLL_ZOOM_FACTOR: f32 = 1.1; // How much one scroll wheel notch changes the distance to the target by
+    const PIXELS_PER_LINE: f32 = 20.0; // For converting touchpad (pixel) scrolling to scroll wheel notches
+    const MIN_VELOCITY: f32 = 0.005; // Radians per second below which inertia stops rather than creeping on forever
+
+    pub fn new(
+        rotate_sensitivity: f32,
+        min_distance: f32,
+        max_distance: f32,
+    ) -> Self {
+        Self {
+            rotate_sensitivity,
+            pan_sensitivity: 0.002,
+            min_distance,
+            max_distance,
+            inertia: 0.0,
+            is_rotating: false,
+            is_panning: false,
+            cursor_position: None,
+            rotate_delta: (0.0, 0.0),
+            pan_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            velocity: (0.0, 0.0),
+        }
+    }
+
+    pub fn with_inertia(
+        mut self,
+        inertia: f32,
+    ) -> Self {
+        self.inertia = inertia;
+        self
+    }
+
+    // `gui_wants_pointer_input` should come from `EguiRenderer::wants_pointer_input`, so that drags and scrolls over GUI windows
+    // are left to the GUI. A drag that started outside the GUI carries on even if the cursor moves over it
+    pub fn process_events(
+        &mut self,
+        event: &WindowEvent,
+        gui_wants_pointer_input: bool,
+    ) {
+        match event {
+            WindowEvent::MouseInput { button, state, .. } => {
+                let is_pressed = *state == ElementState::Pressed;
+                let drag = match button {
+                    MouseButton::Left => &mut self.is_rotating,
+                    MouseButton::Middle => &mut self.is_panning,
+                    _ => return,
+                };
+                if !is_pressed {
+                    *drag = false;
+                } else if !gui_wants_pointer_input {
+                    *drag = true;
+                    // Grabbing the camera stops any rotation left over from the last drag
+                    self.velocity = (0.0, 0.0);
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(last_position) = self.cursor_position {
+                    let delta = ((position.x - last_position.x) as f32, (position.y - last_position.y) as f32);
+                    if self.is_rotating {
+                        self.rotate_delta.0 += delta.0;
+                        self.rotate_delta.1 += delta.1;
+                    }
+                    if self.is_panning {
+                        self.pan_delta.0 += delta.0;
+                        self.pan_delta.1 += delta.1;
+                    }
+                }
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                // Otherwise the cursor coming back in somewhere else would look like a big drag
+                self.cursor_position = None;
+            }
+            WindowEvent::MouseWheel { delta, .. } if !gui_wants_pointer_input => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / Self::PIXELS_PER_LINE,
+                };
+            }
+            // Losing focus means we won't hear about buttons being released
+            WindowEvent::Focused(false) => {
+                self.is_rotating = false;
+                self.is_panning = false;
+            }
+            _ => {},
+        }
+    }
+
+    // `delta_time` is in seconds. Dragging moves the camera by however far the cursor moved, but inertia carries on at the speed
+    // of the drag, so it coasts the same distance at any frame rate
+    pub fn update_camera(
+        &mut self,
+        camera: &mut Camera,
+        delta_time: f32,
+    ) {
+        use cgmath::InnerSpace;
+        let offset = camera.eye - camera.target;
+        let mut distance = offset.magnitude();
+        // An eye right on top of the target, e.g. from a bookmark or path keyframe, gives no direction, so back off along +z
+        let direction = if distance < f32::EPSILON {
+            distance = self.min_distance;
+            cgmath::Vector3::unit_z()
+        } else {
+            offset / distance
+        };
+
+        // Work out where on the sphere around the target the camera is, so we pick up where any other controller left off
+        let mut yaw = direction.z.atan2(direction.x);
+        let mut pitch = direction.y.clamp(-1.0, 1.0).asin();
+
+        let rotation = if self.is_rotating {
+            let rotation = (self.rotate_delta.0 * self.rotate_sensitivity, self.rotate_delta.1 * self.rotate_sensitivity);
+            if delta_time > 0.0 {
+                self.velocity = (rotation.0 / delta_time, rotation.1 / delta_time);
+            }
+            rotation
+        } else if self.inertia > 0.0 {
+            // Integrate the exponential decay over the whole time step rather than sampling it once per update,
+            // otherwise lower frame rates would coast less far
+            let decay_rate = std::f32::consts::LN_2 / self.inertia;
+            let decay = (-decay_rate * delta_time).exp();
+            let rotation = (
+                self.velocity.0 * (1.0 - decay) / decay_rate,
+                self.velocity.1 * (1.0 - decay) / decay_rate,
+            );
+            self.velocity = (self.velocity.0 * decay, self.velocity.1 * decay);
+            if self.velocity.0.abs() < Self::MIN_VELOCITY && self.velocity.1.abs() < Self::MIN_VELOCITY {
+                self.velocity = (0.0, 0.0);
+            }
+            rotation
+        } else {
+            self.velocity = (0.0, 0.0);
+            (0.0, 0.0)
+        };
+        // Dragging right swings the camera to the left, so the scene appears to turn with the cursor
+        yaw += rotation.0;
+        pitch = (pitch + rotation.1).clamp(-Self::SAFE_PITCH, Self::SAFE_PITCH);
+
+        distance = (distance * Self::SCROLL_ZOOM_FACTOR.powf(-self.scroll_delta)).clamp(self.min_distance, self.max_distance);
+
+        let forward = -cgmath::Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        let pan_scale = distance * self.pan_sensitivity;
+        camera.target += (up * self.pan_delta.1 - right * self.pan_delta.0) * pan_scale;
+        camera.eye = camera.target - forward * distance;
+
+        self.rotate_delta = (0.0, 0.0);
+        self.pan_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
     }
 }
 
-pub struct CameraController {
-    speed: f32,
+// A first-person controller: WASD to move relative to where the camera is looking, Space/E and Q/Ctrl to move up and down,
+// Shift to sprint and the scroll wheel to change speed. Holding the right mouse button turns the camera with the mouse
+pub struct FlyCameraController {
+    speed: f32, // Units per second
+    sprint_multiplier: f32,
+    sensitivity: f32, // Radians per unit of mouse motion
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
     is_right_pressed: bool,
+    is_up_pressed: bool,
+    is_down_pressed: bool,
+    is_sprint_pressed: bool,
+    is_mouse_look_active: bool,
+    mouse_delta: (f64, f64), // Accumulated since the last `update_camera`
 }
 
-impl CameraController {
+impl FlyCameraController {
+    const SAFE_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.001; // Looking straight up or down makes `look_at_rh` degenerate
+    const SCROLL_SPEED_FACTOR: f32 = 1.1; // How much one scroll wheel notch changes the speed by
+    const PIXELS_PER_LINE: f32 = 20.0; // For converting touchpad (pixel) scrolling to scroll wheel notches
+    const FALLBACK_TARGET_DISTANCE: f32 = 1.0; // For when the target is right on top of the camera
+
     pub fn new(
         speed: f32,
+        sensitivity: f32,
     ) -> Self {
         Self {
             speed,
+            sprint_multiplier: 3.0,
+            sensitivity,
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_sprint_pressed: false,
+            is_mouse_look_active: false,
+            mouse_delta: (0.0, 0.0),
         }
     }
 
+    pub fn speed(
+        &self,
+    ) -> f32 {
+        self.speed
+    }
+
+    // Whether the mouse is currently turning the camera, in which case the cursor should be grabbed (see `AppData::set_cursor_grab`)
+    pub fn is_mouse_look_active(
+        &self,
+    ) -> bool {
+        self.is_mouse_look_active
+    }
+
+    // `gui_wants_pointer_input` should come from `EguiRenderer::wants_pointer_input`, as for `OrbitCameraController::process_events`
     pub fn process_events(
         &mut self,
         event: &WindowEvent,
+        gui_wants_pointer_input: bool,
     ) {
         match event {
             WindowEvent::KeyboardInput {
@@ -109,61 +558,138 @@ impl CameraController {
                     ..
                 },
                 ..
+            } => {
+                self.process_keyboard(*key, *state);
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
             } => {
                 let is_pressed = *state == ElementState::Pressed;
-                match key {
-                    KeyCode::KeyW | KeyCode::ArrowUp => {
-                        self.is_forward_pressed = is_pressed;
-                    }
-                    KeyCode::KeyA | KeyCode::ArrowLeft => {
-                        self.is_left_pressed = is_pressed;
-                    }
-                    KeyCode::KeyS | KeyCode::ArrowDown => {
-                        self.is_backward_pressed = is_pressed;
-                    }
-                    KeyCode::KeyD | KeyCode::ArrowRight => {
-                        self.is_right_pressed = is_pressed;
-                    }
-                    _ => {},
+                if !is_pressed || !gui_wants_pointer_input {
+                    self.is_mouse_look_active = is_pressed;
                 }
             }
+            WindowEvent::MouseWheel { delta, .. } if !gui_wants_pointer_input => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / Self::PIXELS_PER_LINE,
+                };
+                self.speed *= Self::SCROLL_SPEED_FACTOR.powf(notches);
+            }
+            // Losing focus means we won't hear about keys being released
+            WindowEvent::Focused(false) => {
+                *self = Self::new(self.speed, self.sensitivity);
+            }
+            _ => {},
+        }
+    }
+
+    // Split out of `process_events` so movement can be driven without a window, as `KeyEvent`s can't be constructed outside winit
+    pub fn process_keyboard(
+        &mut self,
+        key: KeyCode,
+        state: ElementState,
+    ) {
+        let is_pressed = state == ElementState::Pressed;
+        match key {
+            KeyCode::KeyW | KeyCode::ArrowUp => {
+                self.is_forward_pressed = is_pressed;
+            }
+            KeyCode::KeyA | KeyCode::ArrowLeft => {
+                self.is_left_pressed = is_pressed;
+            }
+            KeyCode::KeyS | KeyCode::ArrowDown => {
+                self.is_backward_pressed = is_pressed;
+            }
+            KeyCode::KeyD | KeyCode::ArrowRight => {
+                self.is_right_pressed = is_pressed;
+            }
+            KeyCode::Space | KeyCode::KeyE => {
+                self.is_up_pressed = is_pressed;
+            }
+            KeyCode::KeyQ | KeyCode::ControlLeft => {
+                self.is_down_pressed = is_pressed;
+            }
+            KeyCode::ShiftLeft | KeyCode::ShiftRight => {
+                self.is_sprint_pressed = is_pressed;
+            }
             _ => {},
         }
     }
 
+    // Mouse look uses raw device motion rather than cursor positions, as the cursor stops moving once it's grabbed
+    pub fn process_device_events(
+        &mut self,
+        event: &DeviceEvent,
+    ) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            if self.is_mouse_look_active {
+                self.mouse_delta.0 += delta.0;
+                self.mouse_delta.1 += delta.1;
+            }
+        }
+    }
+
+    // `delta_time` is in seconds. Mouse look isn't scaled by it, as the mouse delta is already however far the mouse moved
     pub fn update_camera(
-        &self,
+        &mut self,
         camera: &mut Camera,
+        delta_time: f32,
     ) {
         use cgmath::InnerSpace;
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let forward_mag = forward.magnitude();
+        let to_target = camera.target - camera.eye;
+        let mut target_distance = to_target.magnitude();
+        // A target right on top of the camera, e.g. from a bookmark or path keyframe, gives no direction, so look down -z
+        let forward = if target_distance < f32::EPSILON {
+            target_distance = Self::FALLBACK_TARGET_DISTANCE;
+            -cgmath::Vector3::unit_z()
+        } else {
+            to_target / target_distance
+        };
 
-        // Prevents glitching when the camera gets too close to the
-        // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
+        // Work out yaw and pitch from where the camera is currently looking, so we pick up where any other controller left off
+        let mut yaw = forward.z.atan2(forward.x);
+        let mut pitch = forward.y.clamp(-1.0, 1.0).asin();
+        yaw += self.mouse_delta.0 as f32 * self.sensitivity;
+        pitch -= self.mouse_delta.1 as f32 * self.sensitivity;
+        pitch = pitch.clamp(-Self::SAFE_PITCH, Self::SAFE_PITCH);
+        self.mouse_delta = (0.0, 0.0);
+
+        let forward = cgmath::Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        let right = forward.cross(camera.up).normalize();
+        let up = camera.up.normalize();
+
+        let mut direction = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        if self.is_forward_pressed {
+            direction += forward;
         }
         if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
+            direction -= forward;
         }
-
-        let right = forward_norm.cross(camera.up);
-
-        // Redo radius calc in case the forward/backward is pressed.
-        let forward = camera.target - camera.eye;
-        let forward_mag = forward.magnitude();
-
         if self.is_right_pressed {
-            // Rescale the distance between the target and the eye so 
-            // that it doesn't change. The eye, therefore, still 
-            // lies on the circle made by the target and eye.
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+            direction += right;
         }
         if self.is_left_pressed {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+            direction -= right;
+        }
+        if self.is_up_pressed {
+            direction += up;
+        }
+        if self.is_down_pressed {
+            direction -= up;
+        }
+
+        if direction.magnitude2() > 0.0 {
+            let speed = if self.is_sprint_pressed { self.speed * self.sprint_multiplier } else { self.speed };
+            camera.eye += direction.normalize() * speed * delta_time;
         }
+        // Keep the target the same distance in front of the camera
+        camera.target = camera.eye + forward * target_distance;
     }
 }
- 
\ No newline at end of file
@@ -0,0 +1,98 @@
+// Named camera viewpoints that can be saved to disk and returned to later
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    camera_path::{CameraPath, CameraPathPlayer, Keyframe},
+    error::{Error, Result},
+    resources,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub camera: Camera, // Only the eye, target, up and fovy are restored, the aspect ratio and projection are left as they are
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CameraBookmarks {
+    bookmarks: Vec<Bookmark>,
+}
+
+impl CameraBookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bookmarks(
+        &self,
+    ) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn get(
+        &self,
+        index: usize,
+    ) -> Option<&Bookmark> {
+        self.bookmarks.get(index)
+    }
+
+    // Saves where `camera` is now. A bookmark with the same name is overwritten, otherwise it's added to the end
+    pub fn add(
+        &mut self,
+        name: &str,
+        camera: &Camera,
+    ) {
+        let bookmark = Bookmark { name: name.to_string(), camera: camera.clone() };
+        match self.bookmarks.iter_mut().find(|bookmark| bookmark.name == name) {
+            Some(existing) => *existing = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+    }
+
+    pub fn remove(
+        &mut self,
+        index: usize,
+    ) -> Option<Bookmark> {
+        (index < self.bookmarks.len()).then(|| self.bookmarks.remove(index))
+    }
+
+    // Starts a smooth move from where `camera` is now to bookmark `index`, taking `duration` seconds. The returned player has to be
+    // updated every frame until it stops playing. `camera`'s up vector is switched over straight away, as paths don't interpolate it
+    pub fn transition_to(
+        &self,
+        index: usize,
+        camera: &mut Camera,
+        duration: f32,
+    ) -> Option<CameraPathPlayer> {
+        let bookmark = self.bookmarks.get(index)?;
+        let mut path = CameraPath::new();
+        path.record(0.0, camera);
+        path.add_keyframe(Keyframe::from_camera(duration, &bookmark.camera));
+        camera.set_up(bookmark.camera.up());
+
+        let mut player = CameraPathPlayer::new(path);
+        player.play();
+        Some(player)
+    }
+
+    pub async fn load(
+        file_name: &str,
+        out_dir: Option<&str>,
+    ) -> Result<Self> {
+        let json = resources::load_string(file_name, out_dir).await?;
+        serde_json::from_str(&json).map_err(|e| Error::Parse { path: file_name.to_string(), message: e.to_string() })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(
+        &self,
+        file_name: &str,
+        out_dir: Option<&str>,
+    ) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Save { path: file_name.to_string(), message: e.to_string() })?;
+        resources::save_string(file_name, &json, out_dir)
+    }
+}
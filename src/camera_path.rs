@@ -0,0 +1,279 @@
+// Recorded camera fly-throughs: a list of timed keyframes that the camera can be moved along, for repeatable demos and benchmark runs
+
+use cgmath::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    error::{Error, Result},
+    resources,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32, // Seconds from the start of the path
+    pub eye: cgmath::Point3<f32>,
+    pub target: cgmath::Point3<f32>,
+    pub fovy: f32,
+}
+
+impl Keyframe {
+    pub fn from_camera(
+        time: f32,
+        camera: &Camera,
+    ) -> Self {
+        Self {
+            time,
+            eye: camera.eye(),
+            target: camera.target(),
+            fovy: camera.fovy(),
+        }
+    }
+}
+
+// Between keyframes, the eye follows a Catmull-Rom spline through the keyframe positions, the view direction is slerped
+// and the distance to the target and the field of view are interpolated linearly
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>, // Sorted by time
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keyframes(
+        &self,
+    ) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    // Keyframes can be added in any order. One at the same time as an existing keyframe replaces it
+    pub fn add_keyframe(
+        &mut self,
+        keyframe: Keyframe,
+    ) {
+        match self.keyframes.binary_search_by(|k| k.time.total_cmp(&keyframe.time)) {
+            Ok(index) => self.keyframes[index] = keyframe,
+            Err(index) => self.keyframes.insert(index, keyframe),
+        }
+    }
+
+    // Adds a keyframe for where `camera` is now
+    pub fn record(
+        &mut self,
+        time: f32,
+        camera: &Camera,
+    ) {
+        self.add_keyframe(Keyframe::from_camera(time, camera));
+    }
+
+    pub fn clear(
+        &mut self,
+    ) {
+        self.keyframes.clear();
+    }
+
+    // Time of the last keyframe
+    pub fn duration(
+        &self,
+    ) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // The interpolated camera at `time`, which is clamped to the path. `None` if the path has no keyframes
+    pub fn sample(
+        &self,
+        time: f32,
+    ) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(Keyframe { time, ..*first });
+        }
+        if time >= last.time {
+            return Some(Keyframe { time, ..*last });
+        }
+
+        // The segment `time` falls in is between keyframes `i` and `i + 1`. Its neighbours shape the spline, and are
+        // repeated at either end of the path
+        let i = self.keyframes.partition_point(|keyframe| keyframe.time <= time) - 1;
+        let k1 = &self.keyframes[i];
+        let k2 = &self.keyframes[i + 1];
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        let eye = cgmath::Point3::from_vec(catmull_rom(
+            k0.eye.to_vec(), k1.eye.to_vec(), k2.eye.to_vec(), k3.eye.to_vec(), t,
+        ));
+
+        let from = k1.target - k1.eye;
+        let to = k2.target - k2.eye;
+        let target = if from.magnitude() < f32::EPSILON || to.magnitude() < f32::EPSILON {
+            // A keyframe with its target right on top of its eye gives no direction to turn from or to
+            k1.target + (k2.target - k1.target) * t
+        } else {
+            let rotation = cgmath::Quaternion::one().slerp(cgmath::Quaternion::from_arc(from.normalize(), to.normalize(), None), t);
+            let distance = from.magnitude() + (to.magnitude() - from.magnitude()) * t;
+            eye + rotation.rotate_vector(from.normalize()) * distance
+        };
+
+        let fovy = k1.fovy + (k2.fovy - k1.fovy) * t;
+        Some(Keyframe { time, eye, target, fovy })
+    }
+
+    // Moves `camera` to where the path is at `time`. Does nothing if the path has no keyframes
+    pub fn apply(
+        &self,
+        time: f32,
+        camera: &mut Camera,
+    ) {
+        if let Some(keyframe) = self.sample(time) {
+            camera.set_eye(keyframe.eye);
+            camera.set_target(keyframe.target);
+            camera.set_fovy(keyframe.fovy);
+        }
+    }
+
+    pub async fn load(
+        file_name: &str,
+        out_dir: Option<&str>,
+    ) -> Result<Self> {
+        let json = resources::load_string(file_name, out_dir).await?;
+        serde_json::from_str(&json).map_err(|e| Error::Parse { path: file_name.to_string(), message: e.to_string() })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(
+        &self,
+        file_name: &str,
+        out_dir: Option<&str>,
+    ) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Save { path: file_name.to_string(), message: e.to_string() })?;
+        resources::save_string(file_name, &json, out_dir)
+    }
+}
+
+// Uniform Catmull-Rom spline between `p1` and `p2`, with `t` in [0, 1]
+fn catmull_rom(
+    p0: cgmath::Vector3<f32>,
+    p1: cgmath::Vector3<f32>,
+    p2: cgmath::Vector3<f32>,
+    p3: cgmath::Vector3<f32>,
+    t: f32,
+) -> cgmath::Vector3<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+// Plays a `CameraPath` back in real time
+pub struct CameraPathPlayer {
+    pub path: CameraPath,
+    pub is_looping: bool, // Whether to start again from the beginning after the last keyframe, rather than stopping there
+    time: f32,
+    is_playing: bool,
+}
+
+impl CameraPathPlayer {
+    pub fn new(
+        path: CameraPath,
+    ) -> Self {
+        Self {
+            path,
+            is_looping: false,
+            time: 0.0,
+            is_playing: false,
+        }
+    }
+
+    pub fn with_looping(
+        mut self,
+        is_looping: bool,
+    ) -> Self {
+        self.is_looping = is_looping;
+        self
+    }
+
+    pub fn time(
+        &self,
+    ) -> f32 {
+        self.time
+    }
+
+    pub fn is_playing(
+        &self,
+    ) -> bool {
+        self.is_playing
+    }
+
+    // Playing a path that already finished starts it again from the beginning
+    pub fn play(
+        &mut self,
+    ) {
+        if self.time >= self.path.duration() {
+            self.time = 0.0;
+        }
+        self.is_playing = true;
+    }
+
+    pub fn pause(
+        &mut self,
+    ) {
+        self.is_playing = false;
+    }
+
+    pub fn toggle_play(
+        &mut self,
+    ) {
+        if self.is_playing {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    // Pauses and rewinds to the start
+    pub fn stop(
+        &mut self,
+    ) {
+        self.is_playing = false;
+        self.time = 0.0;
+    }
+
+    pub fn seek(
+        &mut self,
+        time: f32,
+    ) {
+        self.time = time.clamp(0.0, self.path.duration());
+    }
+
+    // Advances playback by `delta_time` seconds and moves `camera` along the path. Does nothing while paused,
+    // so the camera can be controlled as usual
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        camera: &mut Camera,
+    ) {
+        if !self.is_playing {
+            return;
+        }
+        let duration = self.path.duration();
+        self.time += delta_time;
+        if self.time >= duration {
+            if self.is_looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.is_playing = false;
+            }
+        }
+        self.path.apply(self.time, camera);
+    }
+}
@@ -0,0 +1,99 @@
+// Bounding volumes and view frustum tests, for skipping instances the camera can't see
+
+use cgmath::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: cgmath::Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // A sphere around every point, centred on their bounding box. Not the tightest fit, but close enough for culling
+    pub fn from_points(
+        points: impl IntoIterator<Item = cgmath::Point3<f32>>,
+    ) -> Self {
+        let points = points.into_iter().collect::<Vec<_>>();
+        if points.is_empty() {
+            return Self { center: cgmath::Point3::origin(), radius: 0.0 };
+        }
+        let (min, max) = points.iter().fold(
+            (points[0], points[0]),
+            |(min, max), point| (
+                cgmath::Point3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+                cgmath::Point3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+            ),
+        );
+        let center = min.midpoint(max);
+        let radius = points.iter().map(|point| point.distance(center)).fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    // A sphere around the same points after they've been scaled, rotated and then moved by `position`, as an `Instance` does.
+    // Non-uniform scale turns the sphere into an ellipsoid, so this is the sphere around that, stretched by the largest scale
+    pub fn transformed(
+        &self,
+        position: cgmath::Vector3<f32>,
+        rotation: cgmath::Quaternion<f32>,
+        scale: cgmath::Vector3<f32>,
+    ) -> Self {
+        let max_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        Self {
+            center: cgmath::Point3::from_vec(rotation.rotate_vector(self.center.to_vec().mul_element_wise(scale)) + position),
+            radius: self.radius * max_scale,
+        }
+    }
+
+    // A sphere around the same points after `matrix` has been applied to them. Like `transformed`, the radius grows with
+    // the largest scale in `matrix`, so the sphere stays around the points even if the matrix skews them
+    pub fn transformed_by(
+        &self,
+        matrix: &cgmath::Matrix4<f32>,
+    ) -> Self {
+        let max_scale = matrix.x.truncate().magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * max_scale,
+        }
+    }
+}
+
+// The six planes bounding what a camera can see, facing inwards
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    planes: [cgmath::Vector4<f32>; 6], // (normal, distance), so a point p is inside a plane if normal . p + distance >= 0
+}
+
+impl Frustum {
+    // Extracts the planes from a view-projection matrix that maps to wgpu's clip space (depth in [0, 1]).
+    // See: https://www.gribb.com/compgraph/Fast%20Extraction%20of%20Viewing%20Frustum%20Planes%20from%20the%20World-View-Projection%20Matrix.pdf
+    pub fn from_matrix(
+        view_proj: &cgmath::Matrix4<f32>,
+    ) -> Self {
+        let row = |i| view_proj.row(i);
+        let planes = [
+            row(3) + row(0), // Left
+            row(3) - row(0), // Right
+            row(3) + row(1), // Bottom
+            row(3) - row(1), // Top
+            row(2), // Depth 0, the near plane or (with reversed-Z) the far plane
+            row(3) - row(2), // Depth 1, the far plane or (with reversed-Z) the near plane
+        ].map(|plane| {
+            // Normalise so the plane equation gives real distances. An infinite projection has no far plane at all,
+            // which shows up as a zero normal with a positive distance, i.e. everything is inside
+            let length = plane.truncate().magnitude();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Self { planes }
+    }
+
+    // Whether any part of `sphere` might be visible
+    pub fn intersects_sphere(
+        &self,
+        sphere: &BoundingSphere,
+    ) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(sphere.center.to_vec()) + plane.w >= -sphere.radius)
+    }
+}
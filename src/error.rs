@@ -0,0 +1,89 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    AdapterNotFound, // No adapter matched the `AppConfig`, even after falling back
+    RequestDevice(wgpu::RequestDeviceError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    Surface(wgpu::SurfaceError),
+    ResourceNotFound {
+        path: String,
+    },
+    Load { // The resource exists (or might), but couldn't be read
+        path: String,
+        message: String,
+    },
+    Parse {
+        path: String,
+        message: String,
+    },
+    Save {
+        path: String,
+        message: String,
+    },
+    MissingAttachment(&'static str), // A render pass was drawn without an attachment it needs, e.g. "depth texture"
+    GuiWindowNotFound(String),
+    Unsupported(&'static str), // The device is missing something needed, e.g. "compute shaders", as on WebGL
+    Readback(&'static str), // Copying a texture back to the CPU failed, and why
+}
+
+impl fmt::Display for Error {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Error::AdapterNotFound => write!(f, "No suitable adapter found"),
+            Error::RequestDevice(e) => write!(f, "Couldn't create device: {}", e),
+            Error::CreateSurface(e) => write!(f, "Couldn't create surface: {}", e),
+            Error::Surface(e) => write!(f, "Couldn't get surface texture: {}", e),
+            Error::ResourceNotFound { path } => write!(f, "Resource {:?} not found", path),
+            Error::Load { path, message } => write!(f, "Couldn't load {:?}: {}", path, message),
+            Error::Parse { path, message } => write!(f, "Couldn't parse {:?}: {}", path, message),
+            Error::Save { path, message } => write!(f, "Couldn't save {:?}: {}", path, message),
+            Error::MissingAttachment(attachment) => write!(f, "Render pass is missing its {}", attachment),
+            Error::GuiWindowNotFound(name) => write!(f, "No GUI window named {:?}", name),
+            Error::Unsupported(what) => write!(f, "Device doesn't support {}", what),
+            Error::Readback(reason) => write!(f, "Couldn't read back texture: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(
+        &self,
+    ) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::RequestDevice(e) => Some(e),
+            Error::CreateSurface(e) => Some(e),
+            Error::Surface(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(
+        e: wgpu::RequestDeviceError,
+    ) -> Self {
+        Error::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(
+        e: wgpu::CreateSurfaceError,
+    ) -> Self {
+        Error::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for Error {
+    fn from(
+        e: wgpu::SurfaceError,
+    ) -> Self {
+        Error::Surface(e)
+    }
+}
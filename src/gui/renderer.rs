@@ -1,5 +1,4 @@
 use std::sync::Arc;
-use std::collections::HashMap;
 
 use egui::*;
 use egui_wgpu::{Renderer, ScreenDescriptor};
@@ -7,15 +6,19 @@ use wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureFormat, TextureView};
 use winit::event::WindowEvent;
 use winit;
 
-use super::windows::GuiWindow;
+use crate::error::Result;
+use super::windows::{GuiWindow, GuiWindows};
 use super::SendAny;
 
 pub struct EguiRenderer {
     state: egui_winit::State,
     renderer: Renderer,
+    output_depth_format: Option<TextureFormat>, // Kept so `renderer` can be recreated the same way
+    msaa_samples: u32,
     window: Arc<winit::window::Window>,
     pub screen_descriptor: ScreenDescriptor,
-    gui_windows: HashMap<String, Box<dyn GuiWindow>>,
+    gui_windows: GuiWindows,
+    events: Vec<Box<SendAny>>, // Raised by `gui_windows` since the last call to `take_events`
 }
 
 impl EguiRenderer {
@@ -43,34 +46,67 @@ impl EguiRenderer {
             msaa_samples,
         );
 
-        let gui_windows: HashMap<String, Box<dyn GuiWindow>> = HashMap::new();
-
         EguiRenderer {
             state: egui_state,
             renderer: egui_renderer,
+            output_depth_format,
+            msaa_samples,
             window: window,
             screen_descriptor: screen_descriptor,
-            gui_windows,
+            gui_windows: GuiWindows::new(),
+            events: Vec::new(),
         }
     }
 
+    // Recreates egui's GPU state on a new device (e.g. after the old one was lost). GUI windows are kept, but egui's own state
+    // (window positions etc.) is reset, as starting from a fresh context is the only way to get egui to upload its textures again
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        output_color_format: TextureFormat,
+    ) {
+        self.state = egui_winit::State::new(
+            Context::default(),
+            egui::viewport::ViewportId::ROOT,
+            &self.window,
+            Some(self.window.scale_factor() as f32),
+            None,
+        );
+        self.renderer = Renderer::new(
+            device,
+            output_color_format,
+            self.output_depth_format,
+            self.msaa_samples,
+        );
+    }
+
     pub fn add_gui_window(
         &mut self,
         gui_window_name: &str,
         gui_window: Box<dyn GuiWindow>,
     ) {
-        self.gui_windows.insert(gui_window_name.to_string(), gui_window);
+        self.gui_windows.add(gui_window_name, gui_window);
     }
 
     pub fn send_event(
         &mut self,
         gui_window_name: &str,
         event: &SendAny,
+    ) -> Result<()> {
+        self.gui_windows.send_event(gui_window_name, event)
+    }
+
+    pub fn broadcast_event(
+        &mut self,
+        event: &SendAny,
     ) {
-        self.gui_windows
-            .get_mut(gui_window_name)
-            .unwrap()
-            .update(event);
+        self.gui_windows.broadcast_event(event);
+    }
+
+    pub fn take_events(
+        &mut self,
+    ) -> Vec<Box<SendAny>> {
+        std::mem::take(&mut self.events)
     }
 
     pub fn handle_input(
@@ -80,6 +116,13 @@ impl EguiRenderer {
         let _ = self.state.on_window_event(&*self.window, &event);
     }
 
+    // Whether egui wants mouse events for itself, e.g. the cursor is over a GUI window or dragging one
+    pub fn wants_pointer_input(
+        &self,
+    ) -> bool {
+        self.state.egui_ctx().wants_pointer_input()
+    }
+
     pub fn draw(
         &mut self,
         device: &Device,
@@ -96,13 +139,13 @@ impl EguiRenderer {
         let full_output = self.state
             .egui_ctx()
             .run(raw_input, |_ui| {
-                for (_, gui_window) in &mut self.gui_windows {
-                    gui_window.show(&self.state.egui_ctx());
-                }
+                self.gui_windows.show(self.state.egui_ctx());
             });
 
         self.state.handle_platform_output(&self.window, full_output.platform_output);
 
+        self.events.extend(self.gui_windows.take_events());
+
         let tris = self.state
             .egui_ctx()
             .tessellate(full_output.shapes, self.state.egui_ctx().pixels_per_point());
@@ -0,0 +1,65 @@
+use crate::gui::SendAny;
+
+use super::GuiWindow;
+
+pub struct AdapterEvent {
+    pub adapter_info: wgpu::AdapterInfo,
+}
+
+pub struct AdapterWindow {
+    pub adapter_info: wgpu::AdapterInfo,
+}
+
+impl AdapterWindow {
+    pub fn new(
+        adapter_info: wgpu::AdapterInfo,
+    ) -> Self {
+        Self {
+            adapter_info,
+        }
+    }
+}
+
+impl GuiWindow for AdapterWindow {
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+    ) {
+        egui::Window::new("ℹ Adapter")
+            .resizable(true)
+            .vscroll(true)
+            .default_open(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("adapter_info_grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.label(&self.adapter_info.name);
+                        ui.end_row();
+
+                        ui.label("Backend");
+                        ui.label(format!("{:?}", &self.adapter_info.backend));
+                        ui.end_row();
+
+                        ui.label("Device type");
+                        ui.label(format!("{:?}", &self.adapter_info.device_type));
+                        ui.end_row();
+
+                        ui.label("Driver");
+                        ui.label(format!("{} {}", &self.adapter_info.driver, &self.adapter_info.driver_info));
+                        ui.end_row();
+                    });
+            });
+    }
+
+    fn update(
+        &mut self,
+        event: &SendAny,
+    ) {
+        if let Some(adapter_event) = event.downcast_ref::<AdapterEvent>() {
+            self.adapter_info = adapter_event.adapter_info.clone();
+        }
+    }
+}
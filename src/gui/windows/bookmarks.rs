@@ -0,0 +1,94 @@
+use crate::gui::SendAny;
+
+use super::GuiWindow;
+
+// Sent to the window whenever the list of camera bookmarks changes
+pub struct BookmarksEvent {
+    pub names: Vec<String>,
+}
+
+// Sent from the window to save the current camera as a bookmark
+pub struct AddBookmarkEvent {
+    pub name: String,
+}
+
+// Sent from the window to move the camera to a bookmark
+pub struct RestoreBookmarkEvent {
+    pub index: usize,
+}
+
+// Sent from the window to delete a bookmark
+pub struct RemoveBookmarkEvent {
+    pub index: usize,
+}
+
+pub struct BookmarksWindow {
+    pub names: Vec<String>,
+    new_name: String,
+    events: Vec<Box<SendAny>>,
+}
+
+impl BookmarksWindow {
+    pub fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            new_name: String::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Default for BookmarksWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuiWindow for BookmarksWindow {
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+    ) {
+        egui::Window::new("🔖 Bookmarks")
+            .resizable(true)
+            .vscroll(true)
+            .default_open(false)
+            .show(ctx, |ui| {
+                for (index, name) in self.names.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        // The first nine can also be restored with the number keys
+                        let label = if index < 9 { format!("{}. {}", index + 1, name) } else { name.clone() };
+                        if ui.button(label).clicked() {
+                            self.events.push(Box::new(RestoreBookmarkEvent { index }));
+                        }
+                        if ui.small_button("🗑").clicked() {
+                            self.events.push(Box::new(RemoveBookmarkEvent { index }));
+                        }
+                    });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.new_name);
+                    if ui.add_enabled(!self.new_name.trim().is_empty(), egui::Button::new("Add")).clicked() {
+                        let name = std::mem::take(&mut self.new_name).trim().to_string();
+                        self.events.push(Box::new(AddBookmarkEvent { name }));
+                    }
+                });
+            });
+    }
+
+    fn update(
+        &mut self,
+        event: &SendAny,
+    ) {
+        if let Some(bookmarks_event) = event.downcast_ref::<BookmarksEvent>() {
+            self.names = bookmarks_event.names.clone();
+        }
+    }
+
+    fn take_events(
+        &mut self,
+    ) -> Vec<Box<SendAny>> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
 use super::SendAny;
 
+pub mod adapter;
+pub mod bookmarks;
 pub mod performance;
+pub mod present_mode;
 pub mod stats;
 
 pub trait GuiWindow {
@@ -13,4 +19,67 @@ pub trait GuiWindow {
         &mut self,
         event: &SendAny,
     );
-}
\ No newline at end of file
+
+    // Events raised by interacting with the window, collected by `EguiRenderer` after every draw
+    fn take_events(
+        &mut self,
+    ) -> Vec<Box<SendAny>> {
+        Vec::new()
+    }
+}
+// The GUI windows an `EguiRenderer` draws, by name. Kept apart from the renderer so events can be routed without a window
+#[derive(Default)]
+pub struct GuiWindows {
+    windows: HashMap<String, Box<dyn GuiWindow>>,
+}
+
+impl GuiWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        &mut self,
+        name: &str,
+        gui_window: Box<dyn GuiWindow>,
+    ) {
+        self.windows.insert(name.to_string(), gui_window);
+    }
+
+    // Fails with `Error::GuiWindowNotFound` rather than panicking if there's no window called `name`, e.g. from a typo
+    pub fn send_event(
+        &mut self,
+        name: &str,
+        event: &SendAny,
+    ) -> Result<()> {
+        self.windows
+            .get_mut(name)
+            .ok_or_else(|| Error::GuiWindowNotFound(name.to_string()))?
+            .update(event);
+        Ok(())
+    }
+
+    pub fn broadcast_event(
+        &mut self,
+        event: &SendAny,
+    ) {
+        for gui_window in self.windows.values_mut() {
+            gui_window.update(event);
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+    ) {
+        for gui_window in self.windows.values_mut() {
+            gui_window.show(ctx);
+        }
+    }
+
+    pub fn take_events(
+        &mut self,
+    ) -> Vec<Box<SendAny>> {
+        self.windows.values_mut().flat_map(|gui_window| gui_window.take_events()).collect()
+    }
+}
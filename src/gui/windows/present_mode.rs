@@ -0,0 +1,74 @@
+use crate::gui::SendAny;
+
+use super::GuiWindow;
+
+// Sent to the window whenever the surface's present mode changes
+pub struct PresentModeEvent {
+    pub present_mode: wgpu::PresentMode,
+    pub available_present_modes: Vec<wgpu::PresentMode>,
+}
+
+// Sent from the window when a different present mode is selected, `App` reconfigures the surface in response
+pub struct SetPresentModeEvent {
+    pub present_mode: wgpu::PresentMode,
+}
+
+pub struct PresentModeWindow {
+    pub present_mode: wgpu::PresentMode,
+    pub available_present_modes: Vec<wgpu::PresentMode>,
+    requested_present_mode: Option<wgpu::PresentMode>,
+}
+
+impl PresentModeWindow {
+    pub fn new() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Fifo,
+            available_present_modes: Vec::new(),
+            requested_present_mode: None,
+        }
+    }
+}
+
+impl Default for PresentModeWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuiWindow for PresentModeWindow {
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+    ) {
+        egui::Window::new("🎞 Present mode")
+            .resizable(true)
+            .vscroll(true)
+            .default_open(false)
+            .show(ctx, |ui| {
+                for present_mode in &self.available_present_modes {
+                    if ui.radio(self.present_mode == *present_mode, format!("{:?}", present_mode)).clicked() {
+                        self.requested_present_mode = Some(*present_mode);
+                    }
+                }
+            });
+    }
+
+    fn update(
+        &mut self,
+        event: &SendAny,
+    ) {
+        if let Some(present_mode_event) = event.downcast_ref::<PresentModeEvent>() {
+            self.present_mode = present_mode_event.present_mode;
+            self.available_present_modes = present_mode_event.available_present_modes.clone();
+        }
+    }
+
+    fn take_events(
+        &mut self,
+    ) -> Vec<Box<SendAny>> {
+        match self.requested_present_mode.take() {
+            Some(present_mode) => vec![Box::new(SetPresentModeEvent { present_mode })],
+            None => Vec::new(),
+        }
+    }
+}
@@ -5,16 +5,22 @@ use super::GuiWindow;
 
 pub struct StatsEvent {
     pub num_instances: u32,
+    pub drawn_instances: u32, // Instances inside the camera's view
+    pub culled_instances: u32, // Instances skipped by frustum culling
 }
 
 pub struct StatsWindow {
     pub num_instances: u32,
+    pub drawn_instances: u32,
+    pub culled_instances: u32,
 }
 
 impl StatsWindow {
     pub fn new() -> Self {
         Self {
             num_instances: 0,
+            drawn_instances: 0,
+            culled_instances: 0,
         }
     }
 }
@@ -37,6 +43,14 @@ impl GuiWindow for StatsWindow {
                         ui.label("Num instances");
                         ui.label(format!("{}", &self.num_instances));
                         ui.end_row();
+
+                        ui.label("Drawn instances");
+                        ui.label(format!("{}", &self.drawn_instances));
+                        ui.end_row();
+
+                        ui.label("Culled instances");
+                        ui.label(format!("{}", &self.culled_instances));
+                        ui.end_row();
                     });
             });
     }
@@ -47,6 +61,8 @@ impl GuiWindow for StatsWindow {
     ) {
         if let Some(stats_event) = event.downcast_ref::<StatsEvent>() {
             self.num_instances = stats_event.num_instances;
+            self.drawn_instances = stats_event.drawn_instances;
+            self.culled_instances = stats_event.culled_instances;
         }
     }
 }
\ No newline at end of file
@@ -1,8 +1,16 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    culling::BoundingSphere,
+    object::Object,
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct RawInstance {
     model: [[f32; 4]; 4],
     normal: [[f32; 3]; 3],
+    tint: [f32; 4],
 }
 
 impl RawInstance {
@@ -52,6 +60,12 @@ impl RawInstance {
                     shader_location:11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // tint
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
             }
     }
@@ -60,16 +74,200 @@ impl RawInstance {
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
-    pub rotation_speed: f32,
+    pub rotation_speed: f32, // Degrees per second, around the axis from the origin to `position`
+    pub scale: cgmath::Vector3<f32>, // Along the model's own axes, applied before `rotation`
+    pub tint: [f32; 4], // Linear RGBA, multiplied with the material's diffuse colour
+    pub velocity: cgmath::Vector3<f32>, // Units per second
+    // Applied after the instance's own transform, e.g. the world matrix of the `SceneGraph` node it's attached to. The rest
+    // of the instance's fields are relative to this
+    pub parent_transform: cgmath::Matrix4<f32>,
+}
+
+// An untinted instance at the origin, at the model's own size and orientation
+impl Default for Instance {
+    fn default() -> Self {
+        use cgmath::{One, SquareMatrix, Zero};
+        Self {
+            position: cgmath::Vector3::zero(),
+            rotation: cgmath::Quaternion::one(),
+            rotation_speed: 0.0,
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            tint: [1.0, 1.0, 1.0, 1.0],
+            velocity: cgmath::Vector3::zero(),
+            parent_transform: cgmath::Matrix4::identity(),
+        }
+    }
 }
 
 impl Instance {
+    // Moves the instance by `velocity` and spins it by `rotation_speed` for `delta_time` seconds. `AnimationPass` does the
+    // same on the GPU
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+    ) {
+        use cgmath::{InnerSpace, Rotation3, Zero};
+        self.position += self.velocity * delta_time;
+        // An instance at the origin has no axis to spin around
+        if self.position.is_zero() {
+            return;
+        }
+        self.rotation = self.rotation * cgmath::Quaternion::from_axis_angle(
+            self.position.normalize(),
+            cgmath::Deg(self.rotation_speed * delta_time),
+        );
+    }
+
+    // Where `model_bounds` ends up once this instance's transform is applied
+    pub fn bounding_sphere(
+        &self,
+        model_bounds: &BoundingSphere,
+    ) -> BoundingSphere {
+        model_bounds.transformed(self.position, self.rotation, self.scale).transformed_by(&self.parent_transform)
+    }
+
     pub fn to_raw(
         &self,
     ) -> RawInstance {
+        use cgmath::{Matrix, SquareMatrix};
+        let scale = cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+        // Non-uniform scale skews normals, so they need the inverse-transpose of the model matrix rather than the model matrix
+        // itself. A model squashed flat along an axis has no inverse, but then it also has no area to light, so any matrix will do
+        let linear = linear_part(&self.parent_transform) * cgmath::Matrix3::from(self.rotation) * cgmath::Matrix3::from_diagonal(self.scale);
+        let normal = linear.invert().map_or(linear, |inverse| inverse.transpose());
         RawInstance {
-            model: (cgmath:: Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)).into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
+            model: (self.parent_transform * cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation) * scale).into(),
+            normal: normal.into(),
+            tint: self.tint,
+        }
+    }
+}
+
+// The rotation and scale of `matrix`, without its translation
+pub(crate) fn linear_part(
+    matrix: &cgmath::Matrix4<f32>,
+) -> cgmath::Matrix3<f32> {
+    cgmath::Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate())
+}
+
+// An object's instances on the GPU, kept from frame to frame. Only the instances the object has marked as dirty are
+// uploaded again, and the buffer grows geometrically so adding instances one at a time doesn't reallocate it every frame
+pub struct InstanceBuffer {
+    buffer: wgpu::Buffer,
+    capacity: usize, // In instances
+    len: usize,
+    label: &'static str,
+    revision: u64, // The `Object::revision` the buffer was last brought up to date with
+}
+
+impl InstanceBuffer {
+    // Uploads every instance of `object`
+    pub fn new(
+        device: &wgpu::Device,
+        object: &Object,
+        label: &'static str,
+    ) -> Self {
+        let instance_data = object.instances().iter().map(Instance::to_raw).collect::<Vec<_>>();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&instance_data),
+            usage: Self::usage(device),
+        });
+        Self {
+            buffer,
+            capacity: instance_data.len(),
+            len: instance_data.len(),
+            label,
+            revision: object.revision(),
+        }
+    }
+
+    pub fn buffer(
+        &self,
+    ) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn len(
+        &self,
+    ) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(
+        &self,
+    ) -> bool {
+        self.len == 0
+    }
+
+    // Bytes into the buffer where instance `index` starts
+    pub fn offset(
+        index: usize,
+    ) -> wgpu::BufferAddress {
+        (index * std::mem::size_of::<RawInstance>()) as wgpu::BufferAddress
+    }
+
+    // Uploads the instances `object` has changed since it last cleared its dirty ranges. Must be called with the same
+    // object the buffer was created for. If the object has cleared changes the buffer hadn't taken in yet, e.g. because
+    // the pass it belongs to skipped a frame, every instance is uploaded again. Calling it again before the object
+    // changes does nothing, so it's cheap to make sure a buffer is up to date
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        object: &Object,
+    ) {
+        if object.revision() == self.revision {
+            return;
+        }
+        let missed_changes = self.revision < object.cleared_revision();
+        self.revision = object.revision();
+        let instances = object.instances();
+        self.len = instances.len();
+        let grow = instances.len() > self.capacity;
+        if grow {
+            // Doubling means a steadily growing object only reallocates a logarithmic number of times
+            self.capacity = instances.len().max(self.capacity * 2);
+            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: Self::offset(self.capacity),
+                usage: Self::usage(device),
+                mapped_at_creation: false,
+            });
+        }
+        // Nothing has been copied over from an old buffer, and the changes missed can no longer be told apart from the
+        // rest, so either way everything is dirty
+        if grow || missed_changes {
+            self.write(queue, instances, 0..instances.len());
+        } else {
+            for dirty in object.dirty_ranges() {
+                self.write(queue, instances, dirty);
+            }
+        }
+    }
+
+    // Storage too where the device has storage buffers, so `AnimationPass` can write instances straight into the buffer
+    fn usage(
+        device: &wgpu::Device,
+    ) -> wgpu::BufferUsages {
+        let usage = wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST;
+        if device.limits().max_storage_buffers_per_shader_stage > 0 {
+            usage | wgpu::BufferUsages::STORAGE
+        } else {
+            usage
+        }
+    }
+
+    fn write(
+        &self,
+        queue: &wgpu::Queue,
+        instances: &[Instance],
+        range: std::ops::Range<usize>,
+    ) {
+        if range.is_empty() {
+            return;
         }
+        let instance_data = instances[range.clone()].iter().map(Instance::to_raw).collect::<Vec<_>>();
+        queue.write_buffer(&self.buffer, Self::offset(range.start), bytemuck::cast_slice(&instance_data));
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,19 @@
+pub mod animation;
 pub mod app;
 pub mod camera;
+pub mod camera_bookmarks;
+pub mod camera_path;
+pub mod culling;
+pub mod error;
 pub mod gui;
 pub mod instance;
 pub mod light;
 pub mod model;
 pub mod object;
+pub mod picking;
 pub mod render_pass;
 pub mod resources;
-pub mod texture;
\ No newline at end of file
+pub mod scene;
+pub mod screenshot;
+pub mod texture;
+pub mod timestep;
\ No newline at end of file
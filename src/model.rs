@@ -1,4 +1,4 @@
-use crate::texture;
+use crate::{culling::BoundingSphere, texture};
 
 use std::ops::Range;
 
@@ -57,6 +57,7 @@ impl Vertex for ModelVertex {
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    pub bounding_sphere: BoundingSphere, // Around every mesh, in model space
 }
 
 pub struct Material {
@@ -156,6 +157,9 @@ pub struct Mesh {
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    // CPU copies of the vertex positions and indices, kept for picking
+    pub positions: Vec<cgmath::Point3<f32>>,
+    pub indices: Vec<u32>,
 }
 
 pub trait DrawModel<'a> {
@@ -1,6 +1,293 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::{model, instance};
 
+// Unchanged instances between changed ones are included in the same dirty range if there are no more than this many of
+// them, as uploading a few extra instances costs less than another upload
+const MAX_CLEAN_GAP: usize = 16;
+
+// Identifies an `Object` for as long as it exists, wherever it moves to in a `Vec<Object>`. Render passes key the GPU
+// buffers they keep for each object by this
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId(u64);
+
+impl ObjectId {
+    fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// Refers to one instance of an `Object` for as long as it's there, wherever removing other instances moves it to. Once the
+// instance is removed the handle stays invalid, even if its slot is reused. Any other object, even one rebuilt from the
+// same instances, treats it as invalid too
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceHandle {
+    object: ObjectId, // The object that gave the handle out
+    slot: u32,
+    generation: u32,
+}
+
+// Where the instance at an index was before the changes since `Object::clear_dirty`, for passes that keep state of their
+// own for each instance on the GPU and need to move it along with the instance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InstanceOrigin {
+    Index(usize), // Moved here from this index
+    New, // Added since, so there's nothing to move
+}
+
+struct Slot {
+    generation: u32, // Bumped when the instance is removed, so old handles to the slot stop working
+    index: Option<usize>, // Where the instance is in `Object::instances`, or `None` if the slot is free
+}
+
+// A model drawn once for every instance. Changes to the instances are tracked, so render passes only have to upload
+// the instances that changed since the last frame. Instances are kept packed together, so removing one moves the last
+// instance into its place, and handles follow them there
 pub struct Object {
     pub model: model::Model,
-    pub instances: Vec<instance::Instance>,
-}
\ No newline at end of file
+    pub frustum_culling: bool, // Whether to skip instances outside the camera's view, when the render pass culls at all
+    id: ObjectId,
+    instances: Vec<instance::Instance>,
+    dirty: Vec<u64>, // One bit per instance, set if it changed since `clear_dirty`
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    instance_slots: Vec<u32>, // The slot of each instance, in the same order as `instances`
+    origins: HashMap<usize, InstanceOrigin>, // Instances added or moved since `clear_dirty`, by where they are now
+    revision: u64, // Bumped by every change to the instances
+    cleared_revision: u64, // `revision` when `clear_dirty` was last called
+}
+
+impl Object {
+    pub fn new(
+        model: model::Model,
+        instances: Vec<instance::Instance>,
+    ) -> Self {
+        let mut object = Self {
+            model,
+            frustum_culling: true,
+            id: ObjectId::next(),
+            instances: Vec::new(),
+            dirty: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            instance_slots: Vec::new(),
+            origins: HashMap::new(),
+            revision: 0,
+            cleared_revision: 0,
+        };
+        object.set_instances(instances);
+        object
+    }
+
+    pub fn id(
+        &self,
+    ) -> ObjectId {
+        self.id
+    }
+
+    pub fn instances(
+        &self,
+    ) -> &[instance::Instance] {
+        &self.instances
+    }
+
+    // Marks just instance `index` as changed, so prefer this over `instances_mut` when changing a few instances
+    pub fn instance_mut(
+        &mut self,
+        index: usize,
+    ) -> &mut instance::Instance {
+        self.mark_dirty(index..index + 1);
+        &mut self.instances[index]
+    }
+
+    // Marks every instance as changed
+    pub fn instances_mut(
+        &mut self,
+    ) -> &mut [instance::Instance] {
+        self.mark_dirty(0..self.instances.len());
+        &mut self.instances
+    }
+
+    // Adds `instance` after the others
+    pub fn push_instance(
+        &mut self,
+        instance: instance::Instance,
+    ) -> InstanceHandle {
+        let index = self.instances.len();
+        self.instances.push(instance);
+        self.origins.insert(index, InstanceOrigin::New);
+        self.mark_dirty(index..index + 1);
+        self.allocate_handle(index)
+    }
+
+    // Removes the instance `handle` refers to, moving the last instance into its place, so only that one instance has
+    // to be uploaded again. Returns `None` if it has already been removed
+    pub fn remove_instance(
+        &mut self,
+        handle: InstanceHandle,
+    ) -> Option<instance::Instance> {
+        let index = self.index_of(handle)?;
+        let slot = &mut self.slots[handle.slot as usize];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.slot);
+
+        let last = self.instances.len() - 1;
+        let instance = self.instances.swap_remove(index);
+        self.instance_slots.swap_remove(index);
+        if index == last {
+            self.origins.remove(&last);
+            self.mark_dirty(0..0); // Just to forget the removed instance's dirty bit
+        } else {
+            self.slots[self.instance_slots[index] as usize].index = Some(index);
+            let origin = self.origins.remove(&last).unwrap_or(InstanceOrigin::Index(last));
+            self.origins.insert(index, origin);
+            self.mark_dirty(index..index + 1);
+        }
+        Some(instance)
+    }
+
+    // Replaces every instance. Handles to the old instances stop working
+    pub fn set_instances(
+        &mut self,
+        instances: Vec<instance::Instance>,
+    ) {
+        for slot in &self.instance_slots {
+            let slot = &mut self.slots[*slot as usize];
+            slot.index = None;
+            slot.generation = slot.generation.wrapping_add(1);
+        }
+        self.free_slots.append(&mut self.instance_slots);
+        self.instances = instances;
+        self.origins = (0..self.instances.len()).map(|index| (index, InstanceOrigin::New)).collect();
+        self.mark_dirty(0..self.instances.len());
+        for index in 0..self.instances.len() {
+            self.allocate_handle(index);
+        }
+    }
+
+    // Changes whenever the instances do, so a copy of them kept elsewhere can tell whether it's still up to date
+    pub fn revision(
+        &self,
+    ) -> u64 {
+        self.revision
+    }
+
+    // The `revision` the instances were at when their changes were last cleared. A copy brought up to date at an older
+    // revision than this has missed changes that `dirty_ranges` no longer covers
+    pub fn cleared_revision(
+        &self,
+    ) -> u64 {
+        self.cleared_revision
+    }
+
+    // Where the instance `handle` refers to is now, or `None` if it has been removed
+    pub fn index_of(
+        &self,
+        handle: InstanceHandle,
+    ) -> Option<usize> {
+        if handle.object != self.id {
+            return None;
+        }
+        let slot = self.slots.get(handle.slot as usize)?;
+        if slot.generation == handle.generation { slot.index } else { None }
+    }
+
+    // The handle of the instance at `index`, e.g. to hold on to one found by picking
+    pub fn handle_at(
+        &self,
+        index: usize,
+    ) -> InstanceHandle {
+        let slot = self.instance_slots[index];
+        InstanceHandle { object: self.id, slot, generation: self.slots[slot as usize].generation }
+    }
+
+    pub fn get(
+        &self,
+        handle: InstanceHandle,
+    ) -> Option<&instance::Instance> {
+        self.index_of(handle).map(|index| &self.instances[index])
+    }
+
+    // Marks just this instance as changed
+    pub fn get_mut(
+        &mut self,
+        handle: InstanceHandle,
+    ) -> Option<&mut instance::Instance> {
+        self.index_of(handle).map(|index| self.instance_mut(index))
+    }
+
+    // The instances added or moved since `clear_dirty`, by where they are now, in order
+    pub fn instance_origins(
+        &self,
+    ) -> Vec<(usize, InstanceOrigin)> {
+        let mut origins = self.origins.iter().map(|(index, origin)| (*index, *origin)).collect::<Vec<_>>();
+        origins.sort_unstable_by_key(|(index, _)| *index);
+        origins
+    }
+
+    // The ranges of instances changed since `clear_dirty` was last called, in order. Ranges close together are merged, so
+    // they can include a few unchanged instances
+    pub fn dirty_ranges(
+        &self,
+    ) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (word_index, word) in self.dirty.iter().enumerate() {
+            // Most words are all clean or all dirty, so only pick through the ones that aren't bit by bit
+            let mut bits = *word;
+            while bits != 0 {
+                let start = word_index * 64 + bits.trailing_zeros() as usize;
+                let run_length = (bits >> bits.trailing_zeros()).trailing_ones() as usize;
+                let end = start + run_length;
+                match ranges.last_mut() {
+                    Some(range) if start - range.end <= MAX_CLEAN_GAP => range.end = end,
+                    _ => ranges.push(start..end),
+                }
+                // Clear the run just handled. Shifting by 64 would overflow, but then the run reached the top of the word
+                bits &= u64::MAX.checked_shl((start + run_length - word_index * 64) as u32).unwrap_or(0);
+            }
+        }
+        ranges
+    }
+
+    // Call once every render pass drawing this object has drawn it, to start tracking changes for the next frame
+    pub fn clear_dirty(
+        &mut self,
+    ) {
+        self.dirty.fill(0);
+        self.origins.clear();
+        self.cleared_revision = self.revision;
+    }
+
+    fn allocate_handle(
+        &mut self,
+        index: usize,
+    ) -> InstanceHandle {
+        let slot = self.free_slots.pop().unwrap_or_else(|| {
+            self.slots.push(Slot { generation: 0, index: None });
+            self.slots.len() as u32 - 1
+        });
+        self.slots[slot as usize].index = Some(index);
+        self.instance_slots.push(slot);
+        InstanceHandle { object: self.id, slot, generation: self.slots[slot as usize].generation }
+    }
+
+    fn mark_dirty(
+        &mut self,
+        range: Range<usize>,
+    ) {
+        self.revision += 1;
+        // Instances may have been added or removed since the last time, and bits past the last instance must stay clear
+        self.dirty.resize(self.instances.len().div_ceil(64), 0);
+        let bits_in_last_word = self.instances.len() - self.dirty.len().saturating_sub(1) * 64;
+        if let Some(last) = self.dirty.last_mut() {
+            *last &= u64::MAX >> (64 - bits_in_last_word);
+        }
+        for index in range {
+            self.dirty[index / 64] |= 1 << (index % 64);
+        }
+    }
+}
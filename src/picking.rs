@@ -0,0 +1,156 @@
+// Finding which instance is under the cursor, by casting a ray from the camera through the scene on the CPU
+
+use cgmath::prelude::*;
+
+use crate::{
+    culling::BoundingSphere,
+    instance::Instance,
+    model::Model,
+    object::Object,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>, // Normalised
+}
+
+impl Ray {
+    pub fn new(
+        origin: cgmath::Point3<f32>,
+        direction: cgmath::Vector3<f32>,
+    ) -> Self {
+        Self { origin, direction: direction.normalize() }
+    }
+
+    pub fn at(
+        &self,
+        distance: f32,
+    ) -> cgmath::Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // Distance along the ray to where it first enters `sphere`, or 0 if it starts inside
+    pub fn intersect_sphere(
+        &self,
+        sphere: &BoundingSphere,
+    ) -> Option<f32> {
+        let to_center = sphere.center - self.origin;
+        let closest_approach = to_center.dot(self.direction);
+        let distance_squared = to_center.magnitude2() - closest_approach * closest_approach;
+        let radius_squared = sphere.radius * sphere.radius;
+        if distance_squared > radius_squared {
+            return None;
+        }
+        let half_chord = (radius_squared - distance_squared).sqrt();
+        let (near, far) = (closest_approach - half_chord, closest_approach + half_chord);
+        if far < 0.0 {
+            None // Behind the ray
+        } else {
+            Some(near.max(0.0))
+        }
+    }
+
+    // Distance along the ray to where it crosses the triangle, from either side.
+    // See: https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    pub fn intersect_triangle(
+        &self,
+        a: cgmath::Point3<f32>,
+        b: cgmath::Point3<f32>,
+        c: cgmath::Point3<f32>,
+    ) -> Option<f32> {
+        const EPSILON: f32 = 1e-7;
+        let edge_1 = b - a;
+        let edge_2 = c - a;
+        let p = self.direction.cross(edge_2);
+        let determinant = edge_1.dot(p);
+        if determinant.abs() < EPSILON {
+            return None; // Parallel to the triangle
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = to_origin.cross(edge_1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge_2.dot(q) * inverse_determinant;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    // Distance along the ray to the nearest triangle of `model` placed by `instance`
+    pub fn intersect_instance_mesh(
+        &self,
+        model: &Model,
+        instance: &Instance,
+    ) -> Option<f32> {
+        // An instance squashed flat along an axis has nothing to hit
+        if instance.scale.x == 0.0 || instance.scale.y == 0.0 || instance.scale.z == 0.0 {
+            return None;
+        }
+        // Nor does one whose parent squashes it flat
+        let inverse_parent = instance.parent_transform.invert()?;
+        // Rather than moving every vertex into the world, move the ray into model space. Scaling the direction too (and not
+        // normalising it afterwards) means a distance along the local ray lands on the same point as along the world ray
+        let parent_origin = inverse_parent.transform_point(self.origin);
+        let parent_direction = inverse_parent.transform_vector(self.direction);
+        let inverse_rotation = instance.rotation.invert();
+        let inverse_scale = cgmath::Vector3::new(1.0 / instance.scale.x, 1.0 / instance.scale.y, 1.0 / instance.scale.z);
+        let local_ray = Ray {
+            origin: cgmath::Point3::from_vec(
+                inverse_rotation.rotate_vector(parent_origin.to_vec() - instance.position).mul_element_wise(inverse_scale),
+            ),
+            direction: inverse_rotation.rotate_vector(parent_direction).mul_element_wise(inverse_scale),
+        };
+        model.meshes.iter()
+            .flat_map(|mesh| mesh.indices.chunks_exact(3).filter_map(|triangle| {
+                local_ray.intersect_triangle(
+                    mesh.positions[triangle[0] as usize],
+                    mesh.positions[triangle[1] as usize],
+                    mesh.positions[triangle[2] as usize],
+                )
+            }))
+            .min_by(f32::total_cmp)
+    }
+}
+
+// Whether `pick` stops at the bounding spheres or tests the actual triangles inside them
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PickPrecision {
+    BoundingSphere, // Cheap, but spheres can overlap neighbours and the hit point is on the sphere rather than the mesh
+    Mesh,
+}
+
+// The nearest instance `ray` hits, as `(object_index, instance_index, hit_point)`
+pub fn pick(
+    ray: &Ray,
+    objects: &[Object],
+    precision: PickPrecision,
+) -> Option<(usize, usize, cgmath::Point3<f32>)> {
+    let mut nearest: Option<(usize, usize, f32)> = None;
+    for (object_index, object) in objects.iter().enumerate() {
+        for (instance_index, instance) in object.instances().iter().enumerate() {
+            // The sphere is always tested first, as it rules out most instances far more cheaply than their triangles
+            let Some(sphere_distance) = ray.intersect_sphere(&instance.bounding_sphere(&object.model.bounding_sphere)) else {
+                continue;
+            };
+            if nearest.is_some_and(|(_, _, nearest_distance)| sphere_distance >= nearest_distance) {
+                continue;
+            }
+            let distance = match precision {
+                PickPrecision::BoundingSphere => Some(sphere_distance),
+                PickPrecision::Mesh => ray.intersect_instance_mesh(&object.model, instance),
+            };
+            if let Some(distance) = distance {
+                if nearest.is_none_or(|(_, _, nearest_distance)| distance < nearest_distance) {
+                    nearest = Some((object_index, instance_index, distance));
+                }
+            }
+        }
+    }
+    nearest.map(|(object_index, instance_index, distance)| (object_index, instance_index, ray.at(distance)))
+}
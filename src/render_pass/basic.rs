@@ -1,22 +1,24 @@
 use std::collections::HashMap;
 use crate::{
     app::AppData,
+    error::{Error, Result},
     camera::{
         self,
         Camera,
         CameraUniform,
+        Projection,
     },
-    instance,
+    instance::{self, InstanceBuffer},
     light,
     model::{
         self,
         DrawLight,
         Vertex
     },
-    object::Object,
+    object::{Object, ObjectId},
     texture::Texture,
 };
-use super::RenderPass;
+use super::{update_instance_buffers, RenderPass};
 use wgpu::util::DeviceExt;
 
 pub struct BasicPass {
@@ -27,7 +29,11 @@ pub struct BasicPass {
     pub global_bind_group_layout: wgpu::BindGroupLayout,
     pub global_bind_group: wgpu::BindGroup,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub instance_buffers: HashMap<usize, wgpu::Buffer>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    projection: Projection, // The depth test in `render_pipeline` was built for this
+    pub instance_buffers: HashMap<ObjectId, InstanceBuffer>,
 }
 
 impl BasicPass {
@@ -114,50 +120,9 @@ impl BasicPass {
             bind_group_layouts: &[&global_bind_group_layout],
             push_constant_ranges: &[],
         });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
-            label: Some("Basic Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &basic_shader,
-                entry_point: "vs_main",
-                buffers: &[model::ModelVertex::describe(), instance::RawInstance::describe()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &basic_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.view_formats[0],
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // Every three vertices will correspond to one triangle
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // Tris are facing forward if vertices are arranged in counter-clockwise order
-                cull_mode: Some(wgpu::Face::Back), // Tris not facing forward should be culled
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            // TODO: May need tgo be changed, see https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/#seeing-the-light
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1, // No multisampling
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let color_format = config.view_formats[0];
+        let projection = camera.projection();
+        let render_pipeline = create_render_pipeline(device, &render_pipeline_layout, &basic_shader, color_format, projection);
 
         let instance_buffers = HashMap::new();
 
@@ -169,9 +134,79 @@ impl BasicPass {
             global_bind_group_layout,
             global_bind_group,
             render_pipeline,
+            render_pipeline_layout,
+            shader: basic_shader,
+            color_format,
+            projection,
             instance_buffers,
         }
     }
+
+    // Call after changing the camera's projection, to switch the depth test over if it went to or from reversed-Z
+    pub fn update_projection(
+        &mut self,
+        device: &wgpu::Device,
+        projection: Projection,
+    ) {
+        if projection.is_reversed_z() != self.projection.is_reversed_z() {
+            self.render_pipeline = create_render_pipeline(device, &self.render_pipeline_layout, &self.shader, self.color_format, projection);
+        }
+        self.projection = projection;
+    }
+}
+
+// Separate from `BasicPass::new` so the pipeline can be rebuilt when the depth test changes
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    projection: Projection,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
+        label: Some("Basic Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[model::ModelVertex::describe(), instance::RawInstance::describe()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList, // Every three vertices will correspond to one triangle
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw, // Tris are facing forward if vertices are arranged in counter-clockwise order
+            cull_mode: Some(wgpu::Face::Back), // Tris not facing forward should be culled
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        // TODO: May need tgo be changed, see https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/#seeing-the-light
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: projection.depth_compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1, // No multisampling
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
 }
 
 impl RenderPass for BasicPass {
@@ -182,7 +217,9 @@ impl RenderPass for BasicPass {
         mut encoder: wgpu::CommandEncoder,
         objects: &Vec<Object>,
         depth_texture: Option<&Texture>,
-    ) -> Result<wgpu::CommandEncoder, wgpu::SurfaceError> {
+    ) -> Result<wgpu::CommandEncoder> {
+        let depth_texture = depth_texture.ok_or(Error::MissingAttachment("depth texture"))?;
+
         // Create a `RenderPass` to clear and render the frame
         let clear_color = wgpu::Color {
             r: 0.1,
@@ -208,7 +245,7 @@ impl RenderPass for BasicPass {
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &depth_texture.unwrap().view,
+                view: &depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Load,
                     store: wgpu::StoreOp::Store,
@@ -220,26 +257,18 @@ impl RenderPass for BasicPass {
         });
         render_pass.set_pipeline(&self.render_pipeline);
 
-        for (object_idx, object) in objects.iter().enumerate() {
-            let create_instance_buffer = || {
-                let instance_data = object.instances.iter().map(instance::Instance::to_raw).collect::<Vec<_>>();
-                app_data.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Basic Instance Buffer"),
-                        contents: bytemuck::cast_slice(&instance_data),
-                        usage: wgpu::BufferUsages::VERTEX,
-                })
-            };
-            self.instance_buffers
-                .entry(object_idx)
-                .and_modify(|value| {*value = create_instance_buffer()})
-                .or_insert_with(create_instance_buffer);
-        }
+        update_instance_buffers(&mut self.instance_buffers, app_data, objects, "Basic Instance Buffer");
 
-        for (object_idx, object) in objects.iter().enumerate() {
-            render_pass.set_vertex_buffer(1, self.instance_buffers[&object_idx].slice(..));
+        for object in objects {
+            let instance_buffer = &self.instance_buffers[&object.id()];
+            // Empty buffers can't be bound
+            if instance_buffer.is_empty() {
+                continue;
+            }
+            render_pass.set_vertex_buffer(1, instance_buffer.buffer().slice(..InstanceBuffer::offset(instance_buffer.len())));
             render_pass.draw_light_model_instanced(
                 &object.model,
-                0..object.instances.len() as u32,
+                0..instance_buffer.len() as u32,
                 &self.global_bind_group,
             );
         }
@@ -0,0 +1,396 @@
+// Draws which instance covers each pixel, rather than its colour, so the one under the cursor can be picked exactly.
+// Unlike `picking::pick`, this respects occlusion and the real shape of each mesh however many instances overlap
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::task::Poll;
+use crate::{
+    app::AppData,
+    error::{Error, Result},
+    camera::{
+        self,
+        Camera,
+        CameraUniform,
+        Projection,
+    },
+    instance::{self, InstanceBuffer},
+    model::{
+        self,
+        DrawLight,
+        Vertex
+    },
+    object::{Object, ObjectId},
+    texture::Texture,
+};
+use super::RenderPass;
+use wgpu::util::DeviceExt;
+
+// Where the ID sits in the vertex input, after everything `ModelVertex` and `RawInstance` use
+const ID_SHADER_LOCATION: u32 = 15;
+
+pub struct IdPass {
+    pub camera_uniform: CameraUniform,
+    camera_uniform_buffer: wgpu::Buffer,
+    pub global_bind_group_layout: wgpu::BindGroupLayout,
+    pub global_bind_group: wgpu::BindGroup,
+    pub render_pipeline: wgpu::RenderPipeline,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    projection: Projection, // The depth test in `render_pipeline` was built for this
+    // The ID given to the first instance of each object in the last draw. IDs count up through every instance
+    // of every object, starting from 1 so that 0 can mean nothing was drawn
+    first_ids: Vec<u32>,
+}
+
+impl IdPass {
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &Camera,
+    ) -> Self {
+        let id_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("ID Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/id.wgsl").into()),
+        });
+
+        let global_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ID Globals Layout"),
+            entries: &[
+                // Camera
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        });
+        // Set up camera and create buffer
+        let mut camera_uniform = camera::CameraUniform::new();
+        camera_uniform.update_view_proj(camera);
+        let camera_uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("ID Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        let global_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ID Globals"),
+            layout: &global_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_uniform_buffer.as_entire_binding(),
+                },
+            ]
+        });
+        // Set up render pipeline
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("ID Render Pipeline Layout"),
+            bind_group_layouts: &[&global_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let projection = camera.projection();
+        let render_pipeline = create_render_pipeline(device, &render_pipeline_layout, &id_shader, projection);
+
+        Self {
+            camera_uniform,
+            camera_uniform_buffer,
+            global_bind_group_layout,
+            global_bind_group,
+            render_pipeline,
+            render_pipeline_layout,
+            shader: id_shader,
+            projection,
+            first_ids: Vec::new(),
+        }
+    }
+
+    // Call after changing the camera's projection, to switch the depth test over if it went to or from reversed-Z
+    pub fn update_projection(
+        &mut self,
+        device: &wgpu::Device,
+        projection: Projection,
+    ) {
+        if projection.is_reversed_z() != self.projection.is_reversed_z() {
+            self.render_pipeline = create_render_pipeline(device, &self.render_pipeline_layout, &self.shader, projection);
+        }
+        self.projection = projection;
+    }
+
+    // Starts copying the ID at pixel (`x`, `y`) of `id_texture` back to the CPU. Call once the encoder returned by `draw`
+    // has been submitted. Coordinates outside the texture are clamped to its edge
+    pub fn read_id(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id_texture: &Texture,
+        x: u32,
+        y: u32,
+    ) -> IdReadback {
+        let texture = &id_texture.texture;
+        // A single row still has to be padded out to `COPY_BYTES_PER_ROW_ALIGNMENT`
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ID Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("ID Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: x.min(texture.width() - 1),
+                    y: y.min(texture.height() - 1),
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        IdReadback {
+            buffer,
+            receiver,
+            first_ids: self.first_ids.clone(),
+        }
+    }
+}
+
+// Separate from `IdPass::new` so the pipeline can be rebuilt when the depth test changes
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    projection: Projection,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("ID Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                model::ModelVertex::describe(),
+                instance::RawInstance::describe(),
+                // One ID per instance, kept in a buffer of its own so `RawInstance` doesn't grow for every pass
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &[
+                        wgpu::VertexAttribute {
+                            offset: 0,
+                            shader_location: ID_SHADER_LOCATION,
+                            format: wgpu::VertexFormat::Uint32,
+                        },
+                    ],
+                },
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: Texture::ID_FORMAT,
+                blend: None, // Integer formats can't be blended
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back), // The same as `PhongPass`, so the same faces can be picked as are seen
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: projection.depth_compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+impl RenderPass for IdPass {
+    // `view` must be of an `ID_FORMAT` texture (see `Texture::create_id_texture`), and `depth_texture` is cleared, so should
+    // be separate from the one the visible frame is drawn with
+    fn draw(
+        &mut self,
+        app_data: &AppData,
+        view: &wgpu::TextureView,
+        encoder: wgpu::CommandEncoder,
+        objects: &Vec<Object>,
+        depth_texture: Option<&Texture>,
+    ) -> Result<wgpu::CommandEncoder> {
+        // IDs are only drawn now and then, so there's nothing to gain from keeping these between draws
+        let instance_buffers = objects.iter()
+            .map(|object| (object.id(), InstanceBuffer::new(&app_data.device, object, "ID Instance Buffer")))
+            .collect::<HashMap<_, _>>();
+        self.draw_with_instance_buffers(app_data, view, encoder, objects, depth_texture, &instance_buffers)
+    }
+}
+
+impl IdPass {
+    // The same as `draw`, but takes the instances from another pass's buffers rather than uploading them again, e.g.
+    // `PhongPass::instance_buffers` after `AnimationPass` has moved them on, so the IDs land where the instances were drawn
+    pub fn draw_with_instance_buffers(
+        &mut self,
+        app_data: &AppData,
+        view: &wgpu::TextureView,
+        mut encoder: wgpu::CommandEncoder,
+        objects: &[Object],
+        depth_texture: Option<&Texture>,
+        instance_buffers: &HashMap<ObjectId, InstanceBuffer>,
+    ) -> Result<wgpu::CommandEncoder> {
+        let depth_texture = depth_texture.ok_or(Error::MissingAttachment("depth texture"))?;
+
+        app_data.queue.write_buffer(&self.camera_uniform_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+
+        // Every object's IDs go into the same buffer, one after the other, so they can count straight through them
+        self.first_ids.clear();
+        let mut id_data = Vec::new();
+        for object in objects {
+            self.first_ids.push(id_data.len() as u32 + 1);
+            id_data.extend((0..object.instances().len() as u32).map(|i| self.first_ids.last().unwrap() + i));
+        }
+        let id_buffer = app_data.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ID Buffer"),
+            contents: bytemuck::cast_slice(&id_data),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("ID Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), // ID 0, nothing
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.projection.depth_clear_value()),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.render_pipeline);
+
+        let id_size = std::mem::size_of::<u32>() as wgpu::BufferAddress;
+        for (object, first_id) in objects.iter().zip(&self.first_ids) {
+            if object.instances().is_empty() {
+                continue; // Buffer slices can't be empty
+            }
+            let start = (first_id - 1) as wgpu::BufferAddress;
+            let end = start + object.instances().len() as wgpu::BufferAddress;
+            let instance_buffer = &instance_buffers[&object.id()];
+            render_pass.set_vertex_buffer(1, instance_buffer.buffer().slice(..InstanceBuffer::offset(object.instances().len())));
+            render_pass.set_vertex_buffer(2, id_buffer.slice(start * id_size..end * id_size));
+            render_pass.draw_light_model_instanced(
+                &object.model,
+                0..object.instances().len() as u32,
+                &self.global_bind_group,
+            );
+        }
+
+        drop(render_pass); // Need to drop `render_pass` to release the mutable borrow of `encoder` so we can call `encoder.finish()`
+        Ok(encoder)
+    }
+}
+
+// A pixel on its way back from the GPU, started by `IdPass::read_id`
+pub struct IdReadback {
+    buffer: wgpu::Buffer,
+    receiver: mpsc::Receiver<std::result::Result<(), wgpu::BufferAsyncError>>,
+    first_ids: Vec<u32>, // From the draw the pixel was read from, in case the objects have changed since
+}
+
+impl IdReadback {
+    // Checks whether the pixel has arrived, without blocking. Once it has, the result is `Some((object_index, instance_index))`
+    // for the instance covering the pixel, or `None` if there wasn't one. Only returns `Ready` once, so drop the readback after that
+    pub fn poll(
+        &self,
+        device: &wgpu::Device,
+    ) -> Poll<Option<(usize, usize)>> {
+        device.poll(wgpu::Maintain::Poll); // Does nothing on the web, where the browser calls `map_async`'s callback itself
+        match self.receiver.try_recv() {
+            Ok(result) => Poll::Ready(self.decode(result)),
+            Err(mpsc::TryRecvError::Empty) => Poll::Pending,
+            Err(mpsc::TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+
+    // Blocks until the pixel has arrived. Not available on the web, where the GPU can only be waited on asynchronously
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait(
+        self,
+        device: &wgpu::Device,
+    ) -> Option<(usize, usize)> {
+        device.poll(wgpu::Maintain::Wait);
+        let result = self.receiver.recv().ok()?;
+        self.decode(result)
+    }
+
+    fn decode(
+        &self,
+        result: std::result::Result<(), wgpu::BufferAsyncError>,
+    ) -> Option<(usize, usize)> {
+        if let Err(e) = result {
+            log::error!("Couldn't read back ID: {}", e);
+            return None;
+        }
+        let id = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            u32::from_ne_bytes([data[0], data[1], data[2], data[3]])
+        };
+        self.buffer.unmap();
+        if id == 0 {
+            return None;
+        }
+        // Objects without instances share their first ID with the next object, so take the last object starting at or before `id`
+        let object_index = self.first_ids.partition_point(|first_id| *first_id <= id).checked_sub(1)?;
+        Some((object_index, (id - self.first_ids[object_index]) as usize))
+    }
+}
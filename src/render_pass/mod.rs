@@ -1,7 +1,9 @@
-use crate::{object::Object, app::AppData, texture::Texture};
+use std::collections::HashMap;
+use crate::{object::{Object, ObjectId}, app::AppData, error::Result, instance::InstanceBuffer, texture::Texture};
 
 pub mod phong;
 pub mod basic;
+pub mod id;
 
 pub trait RenderPass {
     fn draw(
@@ -11,5 +13,21 @@ pub trait RenderPass {
         encoder: wgpu::CommandEncoder,
         objects: &Vec<Object>,
         depth_texture: Option<&Texture>,
-    ) -> Result<wgpu::CommandEncoder, wgpu::SurfaceError>;
-}
\ No newline at end of file
+    ) -> Result<wgpu::CommandEncoder>;
+}
+
+// Brings the buffers a pass keeps for each object up to date with `objects`, creating them for new objects and
+// dropping those of objects that have gone
+pub(crate) fn update_instance_buffers(
+    instance_buffers: &mut HashMap<ObjectId, InstanceBuffer>,
+    app_data: &AppData,
+    objects: &[Object],
+    label: &'static str,
+) {
+    instance_buffers.retain(|id, _| objects.iter().any(|object| object.id() == *id));
+    for object in objects {
+        instance_buffers.entry(object.id())
+            .and_modify(|instance_buffer| instance_buffer.update(&app_data.device, &app_data.queue, object))
+            .or_insert_with(|| InstanceBuffer::new(&app_data.device, object, label));
+    }
+}
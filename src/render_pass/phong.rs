@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use crate::{
     app::AppData,
+    error::{Error, Result},
     camera::{
         self,
         Camera,
         CameraUniform,
+        Projection,
     },
-    instance,
+    instance::{self, InstanceBuffer},
     light,
     model::{
         self,
@@ -14,10 +16,10 @@ use crate::{
         Material,
         Vertex,
     },
-    object:: Object,
+    object::{Object, ObjectId},
     texture::Texture,
 };
-use super::RenderPass;
+use super::{update_instance_buffers, RenderPass};
 use wgpu::util::DeviceExt;
 
 pub struct PhongPass {
@@ -29,7 +31,25 @@ pub struct PhongPass {
     pub global_bind_group: wgpu::BindGroup,
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub instance_buffers: HashMap<usize, wgpu::Buffer>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    projection: Projection, // The depth test in `render_pipeline` was built for this
+    pub instance_buffers: HashMap<ObjectId, InstanceBuffer>,
+    pub frustum_culling: bool, // Whether to skip instances outside the camera's view
+    pub culling_stats: CullingStats, // From the last `draw`
+}
+
+// Culled instances between visible ones are drawn anyway if there are no more than this many of them, as another draw call
+// costs more than a few instances that the GPU clips straight away
+const MAX_CULLED_GAP: usize = 16;
+
+// How many instances the last `PhongPass::draw` drew, and how many it left out as the camera couldn't see them. Culled
+// instances in the small gaps between visible ones are counted as drawn, as they were
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CullingStats {
+    pub drawn_instances: u32,
+    pub culled_instances: u32,
 }
 
 impl PhongPass {
@@ -119,50 +139,9 @@ impl PhongPass {
             bind_group_layouts: &[&global_bind_group_layout, &texture_bind_group_layout],
             push_constant_ranges: &[],
         });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
-            label: Some("Phong Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &phong_shader,
-                entry_point: "vs_main",
-                buffers: &[model::ModelVertex::describe(), instance::RawInstance::describe()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &phong_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.view_formats[0],
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // Every three vertices will correspond to one triangle
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // Tris are facing forward if vertices are arranged in counter-clockwise order
-                cull_mode: Some(wgpu::Face::Back), // Tris not facing forward should be culled
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            // TODO: May need tgo be changed, see https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/#seeing-the-light
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1, // No multisampling
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let color_format = config.view_formats[0];
+        let projection = camera.projection();
+        let render_pipeline = create_render_pipeline(device, &render_pipeline_layout, &phong_shader, color_format, projection);
 
         let instance_buffers = HashMap::new();
 
@@ -175,9 +154,91 @@ impl PhongPass {
             global_bind_group,
             texture_bind_group_layout,
             render_pipeline,
+            render_pipeline_layout,
+            shader: phong_shader,
+            color_format,
+            projection,
             instance_buffers,
+            frustum_culling: true,
+            culling_stats: CullingStats::default(),
         }
     }
+
+    // Call after changing the camera's projection, to switch the depth test over if it went to or from reversed-Z
+    pub fn update_projection(
+        &mut self,
+        device: &wgpu::Device,
+        projection: Projection,
+    ) {
+        if projection.is_reversed_z() != self.projection.is_reversed_z() {
+            self.render_pipeline = create_render_pipeline(device, &self.render_pipeline_layout, &self.shader, self.color_format, projection);
+        }
+        self.projection = projection;
+    }
+
+    // Brings `instance_buffers` up to date with `objects`. `draw` does this itself, but calling it earlier in the frame
+    // gets at the buffers first, e.g. for `AnimationPass` to write into. `draw` then finds them already up to date
+    pub fn update_instance_buffers(
+        &mut self,
+        app_data: &AppData,
+        objects: &[Object],
+    ) {
+        update_instance_buffers(&mut self.instance_buffers, app_data, objects, "Phong Instance Buffer");
+    }
+}
+
+// Separate from `PhongPass::new` so the pipeline can be rebuilt when the depth test changes
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    projection: Projection,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
+        label: Some("Phong Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[model::ModelVertex::describe(), instance::RawInstance::describe()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList, // Every three vertices will correspond to one triangle
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw, // Tris are facing forward if vertices are arranged in counter-clockwise order
+            cull_mode: Some(wgpu::Face::Back), // Tris not facing forward should be culled
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        // TODO: May need tgo be changed, see https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/#seeing-the-light
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: projection.depth_compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1, // No multisampling
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
 }
 
 impl RenderPass for PhongPass {
@@ -188,7 +249,9 @@ impl RenderPass for PhongPass {
         mut encoder: wgpu::CommandEncoder,
         objects: &Vec<Object>,
         depth_texture: Option<&Texture>,
-    ) -> Result<wgpu::CommandEncoder, wgpu::SurfaceError> {
+    ) -> Result<wgpu::CommandEncoder> {
+        let depth_texture = depth_texture.ok_or(Error::MissingAttachment("depth texture"))?;
+
         // Create a `RenderPass` to clear and render the frame
         let clear_color = wgpu::Color {
             r: 0.1,
@@ -214,9 +277,9 @@ impl RenderPass for PhongPass {
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &depth_texture.unwrap().view,
+                view: &depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(self.projection.depth_clear_value()),
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -226,29 +289,38 @@ impl RenderPass for PhongPass {
         });
         render_pass.set_pipeline(&self.render_pipeline);
 
-        for (object_idx, object) in objects.iter().enumerate() {
-            let create_instance_buffer = || {
-                let instance_data = object.instances.iter().map(instance::Instance::to_raw).collect::<Vec<_>>();
-                app_data.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Phong Instance Buffer"),
-                        contents: bytemuck::cast_slice(&instance_data),
-                        usage: wgpu::BufferUsages::VERTEX,
-                })
-            };
-            self.instance_buffers
-                .entry(object_idx)
-                .and_modify(|value| {*value = create_instance_buffer()})
-                .or_insert_with(create_instance_buffer);
-        }
+        update_instance_buffers(&mut self.instance_buffers, app_data, objects, "Phong Instance Buffer");
 
-        for (object_idx, object) in objects.iter().enumerate() {
-            render_pass.set_vertex_buffer(1, self.instance_buffers[&object_idx].slice(..));
-            render_pass.draw_model_instanced(
-                &object.model,
-                0..object.instances.len() as u32,
-                &self.global_bind_group,
-            );
+        // Every instance stays in the buffer, so rather than uploading just the ones the camera can see, only draw the runs of
+        // them it can see
+        let frustum = self.camera_uniform.frustum();
+        let mut culling_stats = CullingStats::default();
+        for object in objects {
+            let instance_buffer = &self.instance_buffers[&object.id()];
+            let frustum_culling = self.frustum_culling && object.frustum_culling;
+            let mut visible_runs: Vec<std::ops::Range<usize>> = Vec::new();
+            for (i, instance) in object.instances().iter().enumerate() {
+                if frustum_culling && !frustum.intersects_sphere(&instance.bounding_sphere(&object.model.bounding_sphere)) {
+                    continue;
+                }
+                match visible_runs.last_mut() {
+                    Some(run) if i - run.end <= MAX_CULLED_GAP => run.end = i + 1,
+                    _ => visible_runs.push(i..i + 1),
+                }
+            }
+
+            for run in visible_runs {
+                culling_stats.drawn_instances += run.len() as u32;
+                render_pass.set_vertex_buffer(1, instance_buffer.buffer().slice(InstanceBuffer::offset(run.start)..InstanceBuffer::offset(run.end)));
+                render_pass.draw_model_instanced(
+                    &object.model,
+                    0..run.len() as u32,
+                    &self.global_bind_group,
+                );
+            }
         }
+        culling_stats.culled_instances = objects.iter().map(|object| object.instances().len() as u32).sum::<u32>() - culling_stats.drawn_instances;
+        self.culling_stats = culling_stats;
         
         drop(render_pass); // Need to drop `render_pass` to release the mutable borrow of `encoder` so we can call `encoder.finish()`
         Ok(encoder)
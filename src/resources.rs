@@ -4,12 +4,18 @@ use std::{
     io::BufReader,
     io::Cursor,
     fs,
+    sync::Mutex,
 };
 
 use cfg_if::cfg_if;
 use wgpu::util::DeviceExt;
 
-use crate::{model, texture};
+use crate::{
+    culling::BoundingSphere,
+    error::{Error, Result},
+    model,
+    texture,
+};
 
 #[cfg(target_arch = "wasm32")]
 fn format_url(
@@ -25,45 +31,105 @@ fn format_url(
     base.join(file_name).unwrap()
 }
 
+#[cfg(target_arch = "wasm32")]
+fn request_error(
+    file_name: &str,
+    e: reqwest::Error,
+) -> Error {
+    if e.status() == Some(reqwest::StatusCode::NOT_FOUND) {
+        Error::ResourceNotFound { path: file_name.to_string() }
+    } else if e.is_decode() {
+        Error::Parse { path: file_name.to_string(), message: e.to_string() }
+    } else {
+        Error::Load { path: file_name.to_string(), message: e.to_string() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn io_error(
+    file_name: &str,
+    e: std::io::Error,
+) -> Error {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        Error::ResourceNotFound { path: file_name.to_string() }
+    } else {
+        Error::Load { path: file_name.to_string(), message: e.to_string() }
+    }
+}
+
+// Where a resource lives on disk: `res/` under `out_dir`, or under this crate's build directory if `None`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resource_path(
+    file_name: &str,
+    out_dir: Option<&str>,
+) -> std::path::PathBuf {
+    std::path::Path::new(out_dir.unwrap_or(env!("OUT_DIR")))
+        .join("res")
+        .join(file_name)
+}
+
+fn parse_error(
+    file_name: &str,
+    e: impl std::fmt::Display,
+) -> Error {
+    Error::Parse { path: file_name.to_string(), message: e.to_string() }
+}
+
 pub async fn load_string(
     file_name: &str,
     out_dir: Option<&str>,
-) -> anyhow::Result<String> {
+) -> Result<String> {
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             let url = format_url(file_name);
-            let txt = reqwest::get(url)
-                .await?
-                .text()
-                .await?;
+            let txt = async {
+                reqwest::get(url)
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            }.await.map_err(|e| request_error(file_name, e))?;
         } else {
-            let path = std::path::Path::new(out_dir.unwrap_or(env!("OUT_DIR")))
-                .join("res")
-                .join(file_name);
-            let txt = fs::read_to_string(path)?;
+            let path = resource_path(file_name, out_dir);
+            let txt = fs::read_to_string(path).map_err(|e| io_error(file_name, e))?;
         }
     }
 
     Ok(txt)
 }
 
+// Writes a resource that `load_string` can read back. Native only, as the web version can only fetch resources from the server
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_string(
+    file_name: &str,
+    contents: &str,
+    out_dir: Option<&str>,
+) -> Result<()> {
+    let path = resource_path(file_name, out_dir);
+    let save_error = |e: std::io::Error| Error::Save { path: file_name.to_string(), message: e.to_string() };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(save_error)?;
+    }
+    fs::write(path, contents).map_err(save_error)
+}
+
 pub async fn load_binary(
     file_name: &str,
     out_dir: Option<&str>,
-) -> anyhow::Result<Vec<u8>> {
+) -> Result<Vec<u8>> {
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             let url = format_url(file_name);
-            let data = reqwest::get(url)
-                .await?
-                .bytes()
-                .await?
-                .to_vec();
+            let data = async {
+                reqwest::get(url)
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await
+            }.await.map_err(|e| request_error(file_name, e))?.to_vec();
         } else {
-            let path = std::path::Path::new(out_dir.unwrap_or(env!("OUT_DIR")))
-                .join("res")
-                .join(file_name);
-            let data = fs::read(path)?;
+            let path = resource_path(file_name, out_dir);
+            let data = fs::read(path).map_err(|e| io_error(file_name, e))?;
         }
     }
 
@@ -78,19 +144,22 @@ struct Response<T> {
 pub async fn load_json<T>(
     file_name: &str,
     out_dir: Option<&str>,
-) -> anyhow::Result<Vec<T>> where T: serde::de::DeserializeOwned {
+) -> Result<Vec<T>> where T: serde::de::DeserializeOwned {
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             let url = format_url(file_name);
-            let data: Response<T> = reqwest::get(url)
-                .await?
-                .json::<Response<T>>()
-                .await?;
+            let data: Response<T> = async {
+                reqwest::get(url)
+                    .await?
+                    .error_for_status()?
+                    .json::<Response<T>>()
+                    .await
+            }.await.map_err(|e| request_error(file_name, e))?;
         } else {
-            let path = std::path::Path::new(out_dir.unwrap_or(env!("OUT_DIR")))
-                .join("res")
-                .join(file_name);
-            let data: Response<T> = serde_json::from_reader(BufReader::new(fs::File::open(path)?))?;
+            let path = resource_path(file_name, out_dir);
+            let file = fs::File::open(path).map_err(|e| io_error(file_name, e))?;
+            let data: Response<T> = serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| parse_error(file_name, e))?;
         }
     }
 
@@ -104,7 +173,7 @@ pub async fn load_texture(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     out_dir: Option<&str>,
-) -> anyhow::Result<texture::Texture> {
+) -> Result<texture::Texture> {
     let data = load_binary(file_name, out_dir).await?;
     texture::Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
 }
@@ -115,11 +184,14 @@ pub async fn load_model(
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
     out_dir: Option<&str>,
-) -> anyhow::Result<model::Model> {
+) -> Result<model::Model> {
     let obj_text = load_string(file_name, out_dir).await?;
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
 
+    // tobj only lets the material loader return its own error type, so we stash our error here to report it instead
+    let material_error: Mutex<Option<Error>> = Mutex::new(None);
+    let material_error_ref = &material_error;
     let (models, obj_materials) = tobj::load_obj_buf_async(
         &mut obj_reader,
         &tobj::LoadOptions {
@@ -128,13 +200,23 @@ pub async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(&p, out_dir).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+            match load_string(&p, out_dir).await {
+                Ok(mat_text) => tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text))),
+                Err(e) => {
+                    *material_error_ref.lock().unwrap() = Some(e);
+                    Err(tobj::LoadError::OpenFileFailed)
+                }
+            }
         },
-    ).await?;
+    ).await.map_err(|e| parse_error(file_name, e))?;
+
+    if let Some(e) = material_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    let obj_materials = obj_materials.map_err(|e| parse_error(file_name, e))?;
 
     let mut materials = Vec::new();
-    for m in obj_materials? {
+    for m in obj_materials {
         let diffuse_texture = load_texture(&m.diffuse_texture, false, device, queue, out_dir).await?;
         let normal_texture = load_texture(&m.normal_texture, true, device, queue, out_dir).await?;
 
@@ -147,6 +229,10 @@ pub async fn load_model(
         ))
     }
 
+    let bounding_sphere = BoundingSphere::from_points(models.iter().flat_map(|m| {
+        m.mesh.positions.chunks(3).map(|p| cgmath::Point3::new(p[0], p[1], p[2]))
+    }));
+
     let meshes = models.into_iter().map(|m| {
         let mut vertices = (0..m.mesh.positions.len() / 3).map(|i| model::ModelVertex {
             position: [
@@ -225,11 +311,14 @@ pub async fn load_model(
             index_buffer: index_buffer,
             num_elements: m.mesh.indices.len() as u32,
             material: m.mesh.material_id.unwrap_or(0),
+            positions: vertices.iter().map(|vertex| vertex.position.into()).collect(),
+            indices: m.mesh.indices,
         }
     }).collect::<Vec<_>>();
 
     Ok(model::Model {
         meshes,
         materials,
+        bounding_sphere,
     })
 }
\ No newline at end of file
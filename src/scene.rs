@@ -0,0 +1,224 @@
+// A hierarchy of transforms, so instances can move along with whatever they're attached to. Each node's world matrix is
+// its parent's world matrix times its own transform, and is only worked out again when that transform, or an ancestor's,
+// has changed since the last `SceneGraph::update`
+use std::collections::HashMap;
+
+use cgmath::prelude::*;
+
+use crate::object::{InstanceHandle, Object, ObjectId};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+// Scales, then rotates, then moves, the same as an `Instance`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: cgmath::Vector3::zero(),
+            rotation: cgmath::Quaternion::one(),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(
+        &self,
+    ) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+struct Node {
+    transform: Transform, // Relative to the parent
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world_matrix: cgmath::Matrix4<f32>, // As of the last `update`
+    dirty: bool, // `transform` has changed since the last `update`
+    dirty_descendants: bool, // Some node below this one is dirty, so `update` can't skip past it
+    attachments: Vec<(ObjectId, InstanceHandle)>, // Instances that hang from this node
+}
+
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    pub fn add_node(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            transform,
+            parent: None,
+            children: Vec::new(),
+            world_matrix: cgmath::Matrix4::identity(),
+            dirty: false,
+            dirty_descendants: false,
+            attachments: Vec::new(),
+        });
+        self.roots.push(id);
+        self.set_parent(id, parent);
+        id
+    }
+
+    pub fn parent(
+        &self,
+        node: NodeId,
+    ) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    pub fn children(
+        &self,
+        node: NodeId,
+    ) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    // Moves `node`, along with everything below it, under `parent`, or to the top of the graph for `None`. Its transform
+    // stays relative to whatever its parent now is, so it will move unless the old and new parent line up.
+    // Panics if `parent` is `node` or below it, as that would make a loop
+    pub fn set_parent(
+        &mut self,
+        node: NodeId,
+        parent: Option<NodeId>,
+    ) {
+        let mut ancestor = parent;
+        while let Some(id) = ancestor {
+            assert!(id != node, "{:?} can't be parented to itself or its own descendant {:?}", node, parent.unwrap());
+            ancestor = self.nodes[id.0].parent;
+        }
+
+        match self.nodes[node.0].parent {
+            Some(old_parent) => self.nodes[old_parent.0].children.retain(|child| *child != node),
+            None => self.roots.retain(|root| *root != node),
+        }
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(node),
+            None => self.roots.push(node),
+        }
+        self.nodes[node.0].parent = parent;
+        self.mark_dirty(node);
+    }
+
+    pub fn transform(
+        &self,
+        node: NodeId,
+    ) -> &Transform {
+        &self.nodes[node.0].transform
+    }
+
+    // Marks `node`, and so everything below it, as needing its world matrix worked out again
+    pub fn transform_mut(
+        &mut self,
+        node: NodeId,
+    ) -> &mut Transform {
+        self.mark_dirty(node);
+        &mut self.nodes[node.0].transform
+    }
+
+    // As of the last `update`
+    pub fn world_matrix(
+        &self,
+        node: NodeId,
+    ) -> cgmath::Matrix4<f32> {
+        self.nodes[node.0].world_matrix
+    }
+
+    // Where `node`'s origin is in the world, as of the last `update`
+    pub fn world_position(
+        &self,
+        node: NodeId,
+    ) -> cgmath::Point3<f32> {
+        cgmath::Point3::from_vec(self.nodes[node.0].world_matrix.w.truncate())
+    }
+
+    // Hangs the instance `handle` refers to in `object` from `node`, so from the next `update` on it's placed relative to
+    // `node` rather than the world. An instance should only be attached to one node. It stays attached wherever it moves
+    // to in the object, until it's removed
+    pub fn attach(
+        &mut self,
+        node: NodeId,
+        object: &Object,
+        handle: InstanceHandle,
+    ) {
+        self.nodes[node.0].attachments.push((object.id(), handle));
+        // The instance hasn't been given this node's world matrix yet
+        self.mark_dirty(node);
+    }
+
+    // Works out the world matrices of every node that has changed, or is below one that has, and hands them on to the
+    // instances attached to those nodes in `objects`. Only those instances are marked as dirty. Pass every object with
+    // attached instances, as the changes are only handed on once
+    pub fn update<'a>(
+        &mut self,
+        objects: impl IntoIterator<Item = &'a mut Object>,
+    ) {
+        let mut objects = objects.into_iter().map(|object| (object.id(), object)).collect::<HashMap<_, _>>();
+        // (node, parent's world matrix, whether the parent's world matrix changed)
+        let mut stack = self.roots.iter()
+            .map(|root| (*root, cgmath::Matrix4::identity(), false))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world_matrix, parent_changed)) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            if !parent_changed && !node.dirty && !node.dirty_descendants {
+                continue; // Nothing at or below this node has changed
+            }
+            let changed = parent_changed || node.dirty;
+            if changed {
+                node.world_matrix = parent_world_matrix * node.transform.matrix();
+                let world_matrix = node.world_matrix;
+                // Instances that have been removed are let go of here, but only objects that were passed in can be checked
+                node.attachments.retain(|(object_id, handle)| match objects.get_mut(object_id) {
+                    Some(object) => object.get_mut(*handle).map(|instance| instance.parent_transform = world_matrix).is_some(),
+                    None => true,
+                });
+            }
+            node.dirty = false;
+            node.dirty_descendants = false;
+            stack.extend(node.children.iter().map(|child| (*child, node.world_matrix, changed)));
+        }
+    }
+
+    fn mark_dirty(
+        &mut self,
+        node: NodeId,
+    ) {
+        self.nodes[node.0].dirty = true;
+        // Let `update` know to look below each ancestor, stopping at the first that already knows
+        let mut ancestor = self.nodes[node.0].parent;
+        while let Some(id) = ancestor {
+            if self.nodes[id.0].dirty_descendants {
+                break;
+            }
+            self.nodes[id.0].dirty_descendants = true;
+            ancestor = self.nodes[id.0].parent;
+        }
+    }
+}
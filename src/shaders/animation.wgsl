@@ -0,0 +1,139 @@
+// Compute shader
+
+// Layouts match `InstanceState`, `InstanceProperties` and `AnimationParams` in animation.rs
+struct InstanceState {
+    position: vec3<f32>,
+    rotation: vec4<f32>, // Quaternion, (x, y, z) vector then w
+};
+
+struct InstanceProperties {
+    parent_transform: mat4x4<f32>,
+    parent_normal: mat3x3<f32>, // The inverse-transpose of `parent_transform`'s rotation and scale
+    velocity: vec3<f32>,
+    rotation_speed: f32, // Degrees per second
+    scale: vec3<f32>,
+    tint: vec4<f32>,
+};
+
+struct AnimationParams {
+    delta_time: f32,
+    num_instances: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: AnimationParams;
+@group(0) @binding(1)
+var<storage, read_write> states: array<InstanceState>;
+@group(0) @binding(2)
+var<storage, read> properties: array<InstanceProperties>;
+// `RawInstance`s, which can't be declared as a struct here as a `mat3x3` would be padded out to 48 bytes
+@group(0) @binding(3)
+var<storage, read_write> raw_instances: array<f32>;
+
+// Floats in a `RawInstance`: a 4x4 model matrix, a 3x3 normal matrix and an RGBA tint
+const RAW_INSTANCE_FLOATS: u32 = 29u;
+
+fn quat_mul(
+    a: vec4<f32>,
+    b: vec4<f32>,
+) -> vec4<f32> {
+    return vec4<f32>(
+        a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz),
+        a.w * b.w - dot(a.xyz, b.xyz),
+    );
+}
+
+fn quat_from_axis_angle(
+    axis: vec3<f32>,
+    degrees: f32,
+) -> vec4<f32> {
+    let half_angle = radians(degrees) * 0.5;
+    return vec4<f32>(axis * sin(half_angle), cos(half_angle));
+}
+
+fn quat_to_mat3(
+    q: vec4<f32>,
+) -> mat3x3<f32> {
+    let x2 = q.x + q.x;
+    let y2 = q.y + q.y;
+    let z2 = q.z + q.z;
+    let xx = q.x * x2;
+    let xy = q.x * y2;
+    let xz = q.x * z2;
+    let yy = q.y * y2;
+    let yz = q.y * z2;
+    let zz = q.z * z2;
+    let wx = q.w * x2;
+    let wy = q.w * y2;
+    let wz = q.w * z2;
+    return mat3x3<f32>(
+        vec3<f32>(1.0 - yy - zz, xy + wz, xz - wy),
+        vec3<f32>(xy - wz, 1.0 - xx - zz, yz + wx),
+        vec3<f32>(xz + wy, yz - wx, 1.0 - xx - yy),
+    );
+}
+
+fn write_vec3(
+    index: u32,
+    value: vec3<f32>,
+) {
+    raw_instances[index] = value.x;
+    raw_instances[index + 1u] = value.y;
+    raw_instances[index + 2u] = value.z;
+}
+
+fn write_vec4(
+    index: u32,
+    value: vec4<f32>,
+) {
+    raw_instances[index] = value.x;
+    raw_instances[index + 1u] = value.y;
+    raw_instances[index + 2u] = value.z;
+    raw_instances[index + 3u] = value.w;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let i = global_id.x;
+    if i >= params.num_instances {
+        return;
+    }
+    var state = states[i];
+    let props = properties[i];
+
+    // The same steps as `Instance::update`: move, then spin around the axis from the origin to the new position
+    state.position += props.velocity * params.delta_time;
+    if any(state.position != vec3<f32>(0.0)) {
+        let spin = quat_from_axis_angle(normalize(state.position), props.rotation_speed * params.delta_time);
+        state.rotation = normalize(quat_mul(state.rotation, spin));
+    }
+    states[i] = state;
+
+    // The same matrices as `Instance::to_raw`. With a pure rotation R, the inverse-transpose of R * S is R * S^-1, and the
+    // inverse-transpose of the parent's part comes ready-made
+    let rotation = quat_to_mat3(state.rotation);
+    let linear = mat3x3<f32>(rotation[0] * props.scale.x, rotation[1] * props.scale.y, rotation[2] * props.scale.z);
+    var normal = linear;
+    if all(props.scale != vec3<f32>(0.0)) {
+        normal = mat3x3<f32>(rotation[0] / props.scale.x, rotation[1] / props.scale.y, rotation[2] / props.scale.z);
+    }
+    normal = props.parent_normal * normal;
+    let model = props.parent_transform * mat4x4<f32>(
+        vec4<f32>(linear[0], 0.0),
+        vec4<f32>(linear[1], 0.0),
+        vec4<f32>(linear[2], 0.0),
+        vec4<f32>(state.position, 1.0),
+    );
+
+    let base = i * RAW_INSTANCE_FLOATS;
+    write_vec4(base, model[0]);
+    write_vec4(base + 4u, model[1]);
+    write_vec4(base + 8u, model[2]);
+    write_vec4(base + 12u, model[3]);
+    write_vec3(base + 16u, normal[0]);
+    write_vec3(base + 19u, normal[1]);
+    write_vec3(base + 22u, normal[2]);
+    write_vec4(base + 25u, props.tint);
+}
@@ -8,6 +8,7 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) tint: vec4<f32>,
 };
 
 struct CameraUniform {
@@ -39,6 +40,7 @@ struct VertexOutput {
     @location(1) tangent_position: vec3<f32>,
     @location(2) tangent_light_position: vec3<f32>,
     @location(3) tangent_view_position: vec3<f32>,
+    @location(4) tint: vec4<f32>,
 };
 
 @vertex
@@ -78,6 +80,7 @@ fn vs_main(
     out.tangent_position = tangent_matrix * world_position.xyz;
     out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
     out.tangent_light_position = tangent_matrix * light.position;
+    out.tint = instance.tint;
     return out;
 }
 
@@ -97,7 +100,7 @@ var s_normal: sampler;
 fn fs_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
     let object_normal = textureSample(t_normal, s_normal, in.tex_coords);
 
     let ambient_strength = 0.1;
@@ -1,5 +1,5 @@
 use image::GenericImageView;
-use anyhow::*;
+use crate::error::{Error, Result};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -9,6 +9,7 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
 
     pub fn from_bytes(
         device: &wgpu::Device,
@@ -17,7 +18,10 @@ impl Texture {
         label: &str,
         is_normal_map: bool,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = image::load_from_memory(bytes).map_err(|e| Error::Parse {
+            path: label.to_string(),
+            message: e.to_string(),
+        })?;
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
@@ -131,4 +135,82 @@ impl Texture {
 
         Self { texture, view, sampler }
     }
-}
\ No newline at end of file
+
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1), // height or width being 0 may cause crashes
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let description = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            // COPY_SRC so that frames can be read back to the CPU
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &config.view_formats,
+        };
+        let texture = device.create_texture(&description);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(config.view_formats[0]),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+
+    // A target for `IdPass` to write object and instance IDs into, which can be read back one pixel at a time
+    pub fn create_id_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1), // height or width being 0 may cause crashes
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let description = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::ID_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&description);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Integer textures can't be filtered, but `Texture` needs a sampler anyway
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, view, sampler }
+    }
+}
@@ -0,0 +1,74 @@
+// How often `App` calls `Application::update`
+#[derive(Default)]
+pub enum UpdateMode {
+    #[default]
+    Variable, // Once per frame, with a time step equal to the frame time
+    Fixed(FixedTimestep), // Zero or more times per frame, each with the same time step
+}
+
+// Accumulates frame time and hands it out in fixed-size ticks, so the simulation runs at the same speed regardless of frame rate
+// See: https://gafferongames.com/post/fix_your_timestep/
+pub struct FixedTimestep {
+    pub tick_rate: f64, // Ticks per second
+    pub max_steps_per_frame: u32, // Caps the catch-up after a long frame, otherwise slow updates cause ever longer frames
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub const DEFAULT_MAX_STEPS_PER_FRAME: u32 = 8;
+
+    // Panics unless `tick_rate` is positive, as otherwise each tick would be infinitely long, or go back in time
+    pub fn new(
+        tick_rate: f64,
+    ) -> Self {
+        assert!(tick_rate > 0.0, "Fixed timestep tick rate must be positive, got {}", tick_rate);
+        Self {
+            tick_rate,
+            max_steps_per_frame: Self::DEFAULT_MAX_STEPS_PER_FRAME,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn with_max_steps_per_frame(
+        mut self,
+        max_steps_per_frame: u32,
+    ) -> Self {
+        self.max_steps_per_frame = max_steps_per_frame;
+        self
+    }
+
+    // Seconds of simulation time per tick
+    pub fn step(
+        &self,
+    ) -> f64 {
+        1.0 / self.tick_rate
+    }
+
+    // Adds `delta_time` seconds of frame time and returns how many ticks to run this frame.
+    // Time left over beyond `max_steps_per_frame` ticks is dropped rather than carried over.
+    pub fn accumulate(
+        &mut self,
+        delta_time: f64,
+    ) -> u32 {
+        let step = self.step();
+        self.accumulator += delta_time;
+
+        let mut steps = 0;
+        while self.accumulator >= step && steps < self.max_steps_per_frame {
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if self.accumulator >= step {
+            log::debug!("Fixed timestep fell behind, dropping {:.4}s", self.accumulator - self.accumulator % step);
+            self.accumulator %= step;
+        }
+        steps
+    }
+
+    // How far we are between the last tick and the next one, in [0, 1). Render code can use this to blend the previous and current state
+    pub fn alpha(
+        &self,
+    ) -> f64 {
+        self.accumulator / self.step()
+    }
+}
@@ -0,0 +1,22 @@
+use hello_wgpu::{animation::AnimationPass, error::Error};
+
+// A device limited to what WebGL2 offers, which has no storage buffers
+fn webgl2_device() -> wgpu::Device {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
+    let (device, _queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("WebGL2 Limited Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+        },
+        None,
+    )).unwrap();
+    device
+}
+
+#[test]
+fn animation_pass_is_unsupported_without_storage_buffers() {
+    let device = webgl2_device();
+    assert!(matches!(AnimationPass::new(&device), Err(Error::Unsupported(_))));
+}
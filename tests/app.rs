@@ -0,0 +1,28 @@
+use hello_wgpu::app::scaled_physical_size;
+use winit::dpi::PhysicalSize;
+
+#[test]
+fn scaling_up_keeps_the_logical_size() {
+    assert_eq!(scaled_physical_size(PhysicalSize::new(800, 600), 1.0, 2.0), PhysicalSize::new(1600, 1200));
+    assert_eq!(scaled_physical_size(PhysicalSize::new(800, 600), 1.0, 1.5), PhysicalSize::new(1200, 900));
+}
+
+#[test]
+fn scaling_down_keeps_the_logical_size() {
+    assert_eq!(scaled_physical_size(PhysicalSize::new(1600, 1200), 2.0, 1.0), PhysicalSize::new(800, 600));
+    assert_eq!(scaled_physical_size(PhysicalSize::new(1200, 900), 1.5, 1.25), PhysicalSize::new(1000, 750));
+}
+
+#[test]
+fn scaling_rounds_to_the_nearest_pixel() {
+    // 101 * 1.5 = 151.5 and 99 * 1.5 = 148.5, both of which round away from zero
+    assert_eq!(scaled_physical_size(PhysicalSize::new(101, 99), 1.0, 1.5), PhysicalSize::new(152, 149));
+    // 101 / 2 * 1.25 = 63.125 and 99 / 2 * 1.25 = 61.875
+    assert_eq!(scaled_physical_size(PhysicalSize::new(101, 99), 2.0, 1.25), PhysicalSize::new(63, 62));
+}
+
+#[test]
+fn scaling_never_gives_an_empty_size() {
+    assert_eq!(scaled_physical_size(PhysicalSize::new(1, 1), 2.0, 0.5), PhysicalSize::new(1, 1));
+    assert_eq!(scaled_physical_size(PhysicalSize::new(0, 600), 1.0, 2.0), PhysicalSize::new(1, 1200));
+}
@@ -0,0 +1,65 @@
+use cgmath::prelude::*;
+use hello_wgpu::camera::{Camera, FlyCameraController, OrbitCameraController};
+use winit::event::{DeviceId, MouseScrollDelta, TouchPhase, WindowEvent};
+
+fn camera_at(
+    eye: cgmath::Point3<f32>,
+    target: cgmath::Point3<f32>,
+) -> Camera {
+    Camera::new(eye, target, cgmath::Vector3::unit_y(), 16.0 / 9.0, 45.0, 0.1, 100.0)
+}
+
+fn assert_finite(
+    camera: &Camera,
+) {
+    assert!(
+        camera.eye().to_vec().is_finite() && camera.target().to_vec().is_finite(),
+        "camera at {:?} looking at {:?}", camera.eye(), camera.target(),
+    );
+}
+
+fn scroll(
+    notches: f32,
+) -> WindowEvent {
+    WindowEvent::MouseWheel {
+        // Safety: the device ID is only compared against other IDs, never used to look up a real device
+        device_id: unsafe { DeviceId::dummy() },
+        delta: MouseScrollDelta::LineDelta(0.0, notches),
+        phase: TouchPhase::Moved,
+    }
+}
+
+#[test]
+fn fly_camera_survives_target_on_top_of_eye() {
+    let eye = cgmath::Point3::new(1.0, 2.0, 3.0);
+    let mut camera = camera_at(eye, eye);
+    let mut controller = FlyCameraController::new(5.0, 0.003);
+    controller.update_camera(&mut camera, 1.0 / 60.0);
+    assert_finite(&camera);
+    assert_eq!(camera.eye(), eye);
+    assert!(camera.eye().distance(camera.target()) > 0.5);
+}
+
+#[test]
+fn fly_camera_leaves_scrolling_over_the_gui_alone() {
+    let mut controller = FlyCameraController::new(5.0, 0.003);
+    controller.process_events(&scroll(3.0), true);
+    assert_eq!(controller.speed(), 5.0);
+    controller.process_events(&scroll(3.0), false);
+    assert!(controller.speed() > 5.0);
+}
+
+#[test]
+fn orbit_camera_survives_eye_on_top_of_target() {
+    let target = cgmath::Point3::new(1.0, 2.0, 3.0);
+    let mut camera = camera_at(target, target);
+    let mut controller = OrbitCameraController::new(0.005, 1.0, 50.0);
+    controller.update_camera(&mut camera, 1.0 / 60.0);
+    assert_finite(&camera);
+    assert_eq!(camera.target(), target);
+    assert!((camera.eye().distance(target) - 1.0).abs() < 1e-5);
+
+    // And it carries on from there rather than being stuck
+    controller.update_camera(&mut camera, 1.0 / 60.0);
+    assert_finite(&camera);
+}
@@ -0,0 +1,56 @@
+use cgmath::MetricSpace;
+use hello_wgpu::{camera::Camera, camera_bookmarks::CameraBookmarks};
+
+fn camera(
+    eye: [f32; 3],
+    fovy: f32,
+) -> Camera {
+    Camera::new(
+        eye.into(),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        1.0,
+        fovy,
+        0.1,
+        100.0,
+    )
+}
+
+#[test]
+fn transition_ends_at_bookmark() {
+    let mut bookmarks = CameraBookmarks::new();
+    bookmarks.add("side", &camera([5.0, 0.0, 0.0], 60.0));
+    bookmarks.add("top", &camera([0.0, 5.0, 0.1], 30.0));
+    // Same name, so this replaces the first bookmark rather than adding a third
+    bookmarks.add("side", &camera([-5.0, 1.0, 0.0], 50.0));
+    assert_eq!(bookmarks.bookmarks().len(), 2);
+
+    let mut camera = camera([0.0, 0.0, 5.0], 45.0);
+    let mut transition = bookmarks.transition_to(0, &mut camera, 1.0).unwrap();
+    transition.update(0.5, &mut camera);
+    assert!(transition.is_playing());
+    assert!(camera.eye().distance(cgmath::Point3::new(-5.0, 1.0, 0.0)) > 0.1, "transition jumped straight to the bookmark");
+
+    transition.update(0.6, &mut camera);
+    assert!(!transition.is_playing());
+    assert!(camera.eye().distance(cgmath::Point3::new(-5.0, 1.0, 0.0)) < 1e-4);
+    assert!((camera.fovy() - 50.0).abs() < 1e-4);
+
+    assert!(bookmarks.transition_to(2, &mut camera, 1.0).is_none());
+}
+
+#[test]
+fn bookmarks_round_trip_through_json() {
+    let dir = std::env::temp_dir().join(format!("hello-wgpu-bookmarks-{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let mut bookmarks = CameraBookmarks::new();
+    bookmarks.add("side", &camera([5.0, 0.0, 0.0], 60.0));
+    bookmarks.save("bookmarks.json", Some(dir)).unwrap();
+    let loaded = pollster::block_on(CameraBookmarks::load("bookmarks.json", Some(dir))).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let bookmark = loaded.get(0).unwrap();
+    assert_eq!(bookmark.name, "side");
+    assert_eq!(bookmark.camera.eye(), cgmath::Point3::new(5.0, 0.0, 0.0));
+    assert_eq!(bookmark.camera.fovy(), 60.0);
+}
@@ -0,0 +1,108 @@
+use cgmath::prelude::*;
+use hello_wgpu::{
+    camera::Camera,
+    camera_path::{CameraPath, CameraPathPlayer, Keyframe},
+};
+
+const TOLERANCE: f32 = 1e-4;
+
+fn path() -> CameraPath {
+    let mut path = CameraPath::new();
+    // Added out of order on purpose
+    for (time, eye, fovy) in [(2.0, [4.0, 1.0, 0.0], 60.0), (0.0, [0.0, 1.0, 4.0], 45.0), (5.0, [0.0, 3.0, -4.0], 30.0)] {
+        path.add_keyframe(Keyframe {
+            time,
+            eye: eye.into(),
+            target: cgmath::Point3::new(0.0, 0.0, 0.0),
+            fovy,
+        });
+    }
+    path
+}
+
+fn camera() -> Camera {
+    Camera::new(
+        cgmath::Point3::new(0.0, 0.0, 1.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        1.0,
+        45.0,
+        0.1,
+        100.0,
+    )
+}
+
+fn assert_keyframes_close(
+    actual: &Keyframe,
+    expected: &Keyframe,
+) {
+    use cgmath::MetricSpace;
+    assert!(
+        actual.eye.distance(expected.eye) < TOLERANCE
+            && actual.target.distance(expected.target) < TOLERANCE
+            && (actual.fovy - expected.fovy).abs() < TOLERANCE,
+        "expected {:?}, got {:?}", expected, actual,
+    );
+}
+
+#[test]
+fn path_passes_through_keyframes() {
+    let path = path();
+    assert_eq!(path.duration(), 5.0);
+    for keyframe in path.keyframes() {
+        assert_keyframes_close(&path.sample(keyframe.time).unwrap(), keyframe);
+    }
+    // Before the start and after the end, the path stays on its first and last keyframes
+    assert_keyframes_close(&path.sample(-1.0).unwrap(), &path.keyframes()[0]);
+    assert_keyframes_close(&path.sample(10.0).unwrap(), &path.keyframes()[2]);
+}
+
+// A keyframe recorded with the eye on the target has no direction, so the target is blended straight across instead
+#[test]
+fn path_survives_eye_on_top_of_target() {
+    let mut path = CameraPath::new();
+    path.add_keyframe(Keyframe {
+        time: 0.0,
+        eye: cgmath::Point3::new(0.0, 1.0, 4.0),
+        target: cgmath::Point3::new(0.0, 1.0, 4.0),
+        fovy: 45.0,
+    });
+    path.add_keyframe(Keyframe {
+        time: 2.0,
+        eye: cgmath::Point3::new(4.0, 1.0, 0.0),
+        target: cgmath::Point3::new(0.0, 0.0, 0.0),
+        fovy: 45.0,
+    });
+    let middle = path.sample(1.0).unwrap();
+    assert!(middle.eye.to_vec().is_finite() && middle.target.to_vec().is_finite(), "got {:?}", middle);
+    assert!(middle.target.distance(cgmath::Point3::new(0.0, 0.5, 2.0)) < TOLERANCE, "got {:?}", middle);
+    for keyframe in path.keyframes() {
+        assert_keyframes_close(&path.sample(keyframe.time).unwrap(), keyframe);
+    }
+}
+
+#[test]
+fn player_loops_and_stops() {
+    let mut camera = camera();
+    let mut player = CameraPathPlayer::new(path()).with_looping(true);
+    player.play();
+    player.update(6.0, &mut camera);
+    assert!(player.is_playing());
+    assert!((player.time() - 1.0).abs() < TOLERANCE);
+
+    player.is_looping = false;
+    player.update(6.0, &mut camera);
+    assert!(!player.is_playing());
+    assert_keyframes_close(&Keyframe::from_camera(5.0, &camera), &player.path.keyframes()[2]);
+}
+
+#[test]
+fn path_round_trips_through_json() {
+    let dir = std::env::temp_dir().join(format!("hello-wgpu-camera-path-{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let path = path();
+    path.save("path.json", Some(dir)).unwrap();
+    let loaded = pollster::block_on(CameraPath::load("path.json", Some(dir))).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(loaded.keyframes(), path.keyframes());
+}
@@ -0,0 +1,55 @@
+// Fixtures shared by the integration tests. Each test only uses some of them
+#![allow(dead_code)]
+
+use hello_wgpu::{
+    app::AppData,
+    instance::Instance,
+    model::{Material, Model},
+    object::Object,
+    resources,
+};
+
+// The instancing example's resources double as test assets
+const RES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/instancing");
+
+// Just enough of an `AppData` to load models with
+pub fn app_data() -> AppData {
+    app_data_sized(64, 64)
+}
+
+// A headless `AppData` rendering to an offscreen target of the given size
+pub fn app_data_sized(
+    width: u32,
+    height: u32,
+) -> AppData {
+    pollster::block_on(AppData::new_headless(width, height, wgpu::TextureFormat::Rgba8UnormSrgb)).unwrap()
+}
+
+// Loads one of the test assets, creating its materials with `layout`
+pub fn load_model(
+    app_data: &AppData,
+    file_name: &str,
+    layout: &wgpu::BindGroupLayout,
+) -> Model {
+    pollster::block_on(resources::load_model(file_name, &app_data.device, &app_data.queue, layout, Some(RES_DIR))).unwrap()
+}
+
+// The unit cube, with materials for the default `Material` layout
+pub fn cube_model(
+    app_data: &AppData,
+) -> Model {
+    let layout = app_data.device.create_bind_group_layout(&Material::describe());
+    load_model(app_data, "cube.obj", &layout)
+}
+
+// A cube with `num_instances` instances in a row along x, one unit apart
+pub fn cube_object(
+    app_data: &AppData,
+    num_instances: usize,
+) -> Object {
+    let instances = (0..num_instances).map(|i| Instance {
+        position: cgmath::Vector3::new(i as f32, 0.0, 0.0),
+        ..Default::default()
+    }).collect();
+    Object::new(cube_model(app_data), instances)
+}
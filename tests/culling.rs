@@ -0,0 +1,69 @@
+use cgmath::prelude::*;
+use hello_wgpu::{
+    camera::{Camera, Projection},
+    culling::BoundingSphere,
+};
+
+fn camera() -> Camera {
+    // At the origin looking down -z
+    Camera::new(
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Point3::new(0.0, 0.0, -1.0),
+        cgmath::Vector3::unit_y(),
+        1.0,
+        90.0,
+        0.1,
+        100.0,
+    )
+}
+
+fn sphere(
+    center: [f32; 3],
+    radius: f32,
+) -> BoundingSphere {
+    BoundingSphere { center: center.into(), radius }
+}
+
+#[test]
+fn frustum_culls_spheres_outside_each_plane() {
+    for projection in [Projection::Perspective, Projection::InfinitePerspective, Projection::Orthographic { size: 20.0 }] {
+        let frustum = camera().with_projection(projection).frustum();
+        assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, -5.0], 1.0)), "{:?} culled a sphere in front", projection);
+        assert!(!frustum.intersects_sphere(&sphere([0.0, 0.0, 5.0], 1.0)), "{:?} kept a sphere behind", projection);
+        assert!(!frustum.intersects_sphere(&sphere([50.0, 0.0, -5.0], 1.0)), "{:?} kept a sphere off to the right", projection);
+        assert!(!frustum.intersects_sphere(&sphere([0.0, -50.0, -5.0], 1.0)), "{:?} kept a sphere below", projection);
+        // Only partly inside, so it has to be kept
+        assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, 0.5], 1.0)), "{:?} culled a sphere through the near plane", projection);
+    }
+}
+
+#[test]
+fn only_infinite_perspective_has_no_far_plane() {
+    let far_away = sphere([0.0, 0.0, -1000.0], 1.0);
+    assert!(!camera().frustum().intersects_sphere(&far_away));
+    assert!(camera().with_projection(Projection::InfinitePerspective).frustum().intersects_sphere(&far_away));
+}
+
+#[test]
+fn bounding_sphere_contains_points() {
+    let points = [[1.0, 0.0, 0.0], [-1.0, 2.0, 0.0], [0.0, 0.0, 3.0]].map(cgmath::Point3::from);
+    let sphere = BoundingSphere::from_points(points);
+    for point in points {
+        assert!(sphere.center.distance(point) <= sphere.radius + 1e-5);
+    }
+}
+
+#[test]
+fn scaled_bounding_sphere_covers_stretched_points() {
+    let points = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]]
+        .map(cgmath::Point3::from);
+    let position = cgmath::Vector3::new(5.0, 0.0, 0.0);
+    let rotation = cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(90.0));
+    let scale = cgmath::Vector3::new(3.0, 1.0, 0.5);
+    let sphere = BoundingSphere::from_points(points).transformed(position, rotation, scale);
+    assert!((sphere.radius - 3.0).abs() < 1e-5);
+    for point in points {
+        let transformed = cgmath::Point3::from_vec(rotation.rotate_vector(point.to_vec().mul_element_wise(scale)) + position);
+        assert!(sphere.center.distance(transformed) <= sphere.radius + 1e-5, "{:?} is outside {:?}", transformed, sphere);
+    }
+}
@@ -0,0 +1,110 @@
+// Checks that `AppData` can recover from a lost device, using its fault injection hook to lose the device
+
+use std::{cell::RefCell, rc::Rc};
+
+use hello_wgpu::app::{App, AppData, Application, RenderTarget};
+
+#[test]
+fn recreates_lost_device() {
+    let mut app_data = pollster::block_on(AppData::new_headless(4, 4, wgpu::TextureFormat::Rgba8UnormSrgb)).unwrap();
+    assert!(!app_data.is_device_lost());
+
+    app_data.simulate_device_lost();
+    assert!(app_data.is_device_lost());
+
+    pollster::block_on(app_data.recreate_device()).unwrap();
+    assert!(!app_data.is_device_lost());
+
+    // The new device and target should be usable end to end
+    let RenderTarget::Offscreen(target) = &app_data.target else {
+        unreachable!("headless AppData always renders offscreen");
+    };
+    let mut encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Device Lost Test Encoder"),
+    });
+    drop(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Device Lost Test Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &target.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    }));
+    app_data.queue.submit(std::iter::once(encoder.finish()));
+
+    let image = app_data.read_target().unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+}
+
+// What `App` asked of `RecordingApplication`, in order
+#[derive(Debug, PartialEq)]
+enum Call {
+    Render([u8; 4]), // The first pixel of the frame, read back after rendering it
+    Reinit,
+}
+
+// Clears every frame to green, losing the device after the first one
+struct RecordingApplication {
+    calls: Rc<RefCell<Vec<Call>>>,
+}
+
+impl Application for RecordingApplication {
+    fn render(
+        &mut self,
+        app_data: &mut AppData,
+        view: wgpu::TextureView,
+        mut encoder: wgpu::CommandEncoder,
+    ) {
+        drop(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Recording Application Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        }));
+        app_data.queue.submit(std::iter::once(encoder.finish()));
+        let pixel = app_data.read_target().unwrap().get_pixel(0, 0).0;
+
+        let mut calls = self.calls.borrow_mut();
+        calls.push(Call::Render(pixel));
+        if calls.len() == 1 {
+            app_data.simulate_device_lost();
+        }
+    }
+
+    fn reinit(
+        &mut self,
+        app_data: &mut AppData,
+    ) {
+        assert!(!app_data.is_device_lost(), "reinit should only be called once there's a new device");
+        self.calls.borrow_mut().push(Call::Reinit);
+    }
+}
+
+#[test]
+fn app_reinits_application_after_device_loss() {
+    let app_data = pollster::block_on(AppData::new_headless(4, 4, wgpu::TextureFormat::Rgba8UnormSrgb)).unwrap();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut app = pollster::block_on(App::new(RecordingApplication { calls: Rc::clone(&calls) }, app_data));
+
+    // The first frame renders, then loses the device
+    assert!(app.redraw());
+    assert_eq!(*calls.borrow(), vec![Call::Render([0, 255, 0, 255])]);
+
+    // The next recovers it, has the application recreate its resources, and renders on the new device
+    assert!(app.redraw());
+    assert_eq!(*calls.borrow(), vec![Call::Render([0, 255, 0, 255]), Call::Reinit, Call::Render([0, 255, 0, 255])]);
+}
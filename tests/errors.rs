@@ -0,0 +1,49 @@
+// Mistakes in resources or setup that should come back as an `Error` to report, rather than a panic
+mod common;
+
+use hello_wgpu::{
+    error::Error,
+    gui::windows::{stats::{StatsEvent, StatsWindow}, GuiWindows},
+    model::Material,
+    resources,
+};
+
+// A single triangle whose material library isn't there
+const OBJ_WITH_MISSING_MTL: &str = "\
+mtllib missing.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+usemtl missing
+f 1/1/1 2/1/1 3/1/1
+";
+
+#[test]
+fn missing_material_library_is_an_error() {
+    let out_dir = std::env::temp_dir().join(format!("hello-wgpu-errors-{}", std::process::id()));
+    std::fs::create_dir_all(out_dir.join("res")).unwrap();
+    std::fs::write(out_dir.join("res").join("missing_mtl.obj"), OBJ_WITH_MISSING_MTL).unwrap();
+
+    let app_data = common::app_data();
+    let layout = app_data.device.create_bind_group_layout(&Material::describe());
+    let result = pollster::block_on(resources::load_model(
+        "missing_mtl.obj", &app_data.device, &app_data.queue, &layout, Some(out_dir.to_str().unwrap()),
+    ));
+    std::fs::remove_dir_all(&out_dir).unwrap();
+    match result {
+        Err(Error::ResourceNotFound { path }) => assert_eq!(path, "missing.mtl"),
+        Err(e) => panic!("expected the missing .mtl to be reported, got {}", e),
+        Ok(_) => panic!("expected the missing .mtl to be reported, but the model loaded"),
+    }
+}
+
+#[test]
+fn sending_to_a_missing_gui_window_is_an_error() {
+    let mut gui_windows = GuiWindows::new();
+    gui_windows.add("stats", Box::new(StatsWindow::new()));
+    let event = StatsEvent { num_instances: 1, drawn_instances: 1, culled_instances: 0 };
+    assert!(gui_windows.send_event("stats", &event).is_ok());
+    assert!(matches!(gui_windows.send_event("no-such-window", &event), Err(Error::GuiWindowNotFound(name)) if name == "no-such-window"));
+}
@@ -0,0 +1,121 @@
+// Checks that camera and instance motion depends on how much time has passed, not on how many frames it was split into.
+// Each test simulates the same stretch of time at 30 and at 144 frames per second and compares where things end up.
+
+use cgmath::prelude::*;
+use hello_wgpu::{
+    camera::{Camera, FlyCameraController, OrbitCameraController},
+    instance::Instance,
+};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{DeviceId, ElementState, MouseButton, WindowEvent},
+    keyboard::KeyCode,
+};
+
+const SLOW_FPS: u32 = 30;
+const FAST_FPS: u32 = 144;
+const TOLERANCE: f32 = 1e-3;
+
+fn camera() -> Camera {
+    Camera::new(
+        cgmath::Point3::new(0.0, 2.0, 4.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        16.0 / 9.0,
+        45.0,
+        0.1,
+        100.0,
+    )
+}
+
+fn assert_points_close(
+    slow: cgmath::Point3<f32>,
+    fast: cgmath::Point3<f32>,
+) {
+    assert!(
+        slow.distance(fast) < TOLERANCE,
+        "ended up at {:?} at {} FPS but {:?} at {} FPS", slow, SLOW_FPS, fast, FAST_FPS,
+    );
+}
+
+// Flies forward and up while sprinting for two seconds, returning where the camera ends up
+fn fly(
+    fps: u32,
+) -> cgmath::Point3<f32> {
+    let mut camera = camera();
+    let mut controller = FlyCameraController::new(5.0, 0.003);
+    for key in [KeyCode::KeyW, KeyCode::Space, KeyCode::ShiftLeft] {
+        controller.process_keyboard(key, ElementState::Pressed);
+    }
+    for _ in 0..fps * 2 {
+        controller.update_camera(&mut camera, 1.0 / fps as f32);
+    }
+    camera.eye()
+}
+
+// Drags the orbit camera sideways at a steady 300 pixels per second for half a second, then lets inertia carry it for two seconds
+fn orbit(
+    fps: u32,
+) -> cgmath::Point3<f32> {
+    // Safety: the device ID is only compared against other IDs, never used to look up a real device
+    let device_id = unsafe { DeviceId::dummy() };
+    let mouse_input = |state| WindowEvent::MouseInput { device_id, state, button: MouseButton::Left };
+    let cursor_moved = |x| WindowEvent::CursorMoved { device_id, position: PhysicalPosition::new(x, 100.0) };
+
+    let mut camera = camera();
+    let mut controller = OrbitCameraController::new(0.005, 1.0, 50.0).with_inertia(0.1);
+    let delta_time = 1.0 / fps as f32;
+    let mut x = 100.0;
+    controller.process_events(&cursor_moved(x), false);
+    controller.process_events(&mouse_input(ElementState::Pressed), false);
+    for _ in 0..fps / 2 {
+        x += 300.0 / fps as f64;
+        controller.process_events(&cursor_moved(x), false);
+        controller.update_camera(&mut camera, delta_time);
+    }
+    controller.process_events(&mouse_input(ElementState::Released), false);
+    for _ in 0..fps * 2 {
+        controller.update_camera(&mut camera, delta_time);
+    }
+    camera.eye()
+}
+
+#[test]
+fn fly_camera_moves_equally_at_any_frame_rate() {
+    let start = camera().eye();
+    let slow = fly(SLOW_FPS);
+    let fast = fly(FAST_FPS);
+    assert!(slow.distance(start) > 1.0, "camera didn't move");
+    assert_points_close(slow, fast);
+}
+
+#[test]
+fn orbit_camera_inertia_coasts_equally_at_any_frame_rate() {
+    let start = camera().eye();
+    let slow = orbit(SLOW_FPS);
+    let fast = orbit(FAST_FPS);
+    assert!(slow.distance(start) > 1.0, "camera didn't move");
+    assert_points_close(slow, fast);
+}
+
+#[test]
+fn instance_rotates_equally_at_any_frame_rate() {
+    let rotate = |fps: u32| {
+        let mut instance = Instance {
+            position: cgmath::Vector3::new(1.0, 2.0, 3.0),
+            rotation: cgmath::Quaternion::one(),
+            rotation_speed: 45.0,
+            ..Default::default()
+        };
+        for _ in 0..fps * 2 {
+            instance.update(1.0 / fps as f32);
+        }
+        instance.rotation
+    };
+    let slow = rotate(SLOW_FPS);
+    let fast = rotate(FAST_FPS);
+    // Two seconds at 45 degrees per second
+    let expected = cgmath::Quaternion::from_axis_angle(cgmath::Vector3::new(1.0, 2.0, 3.0).normalize(), cgmath::Deg(90.0));
+    assert!(slow.dot(fast).abs() > 1.0 - TOLERANCE, "rotated to {:?} at {} FPS but {:?} at {} FPS", slow, SLOW_FPS, fast, FAST_FPS);
+    assert!(slow.dot(expected).abs() > 1.0 - TOLERANCE, "rotated to {:?}, expected {:?}", slow, expected);
+}
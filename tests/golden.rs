@@ -0,0 +1,363 @@
+// Golden-image regression tests for the render passes.
+// Each test renders a fixed scene offscreen on the software adapter and compares it to a reference PNG in `tests/golden/`.
+// Run with `GOLDEN_UPDATE=1` to (re)write the references after an intentional change to the output.
+// On a mismatch, the rendered frame and a diff image are written to `target/golden/`.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use cgmath::prelude::*;
+use hello_wgpu::{
+    animation::AnimationPass,
+    app::{AppData, RenderTarget},
+    camera::{Camera, Projection},
+    culling::BoundingSphere,
+    instance::Instance,
+    object::Object,
+    render_pass::{basic::BasicPass, phong::PhongPass, RenderPass},
+    texture::Texture,
+};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 192;
+// Largest per-channel difference for a pixel to still count as matching. Leaves room for rasterisation differences between software adapters
+const CHANNEL_TOLERANCE: u8 = 3;
+// Fraction of pixels allowed to exceed `CHANNEL_TOLERANCE` before the test fails
+const MAX_MISMATCHED_FRACTION: f64 = 0.001;
+
+fn camera() -> Camera {
+    Camera::new(
+        cgmath::Point3::new(0.0, 6.0, 10.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        WIDTH as f32 / HEIGHT as f32,
+        45.0,
+        0.1,
+        100.0,
+    )
+}
+
+fn instances() -> Vec<Instance> {
+    (0..3).flat_map(|x| (0..3).map(move |z| {
+        let position = cgmath::Vector3::new(x as f32 * 3.0 - 3.0, 0.0, z as f32 * 3.0 - 3.0);
+        let rotation = cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_y(), cgmath::Deg((x * 3 + z) as f32 * 10.0));
+        Instance { position, rotation, rotation_speed: 0.0, ..Default::default() }
+    })).collect()
+}
+
+fn render(
+    draw: impl FnOnce(&AppData, &wgpu::TextureView, wgpu::CommandEncoder, &Texture) -> wgpu::CommandEncoder,
+) -> image::RgbaImage {
+    let app_data = common::app_data_sized(WIDTH, HEIGHT);
+    let RenderTarget::Offscreen(target) = &app_data.target else {
+        unreachable!("headless AppData always renders offscreen");
+    };
+    let depth_texture = Texture::create_depth_texture(&app_data.device, &app_data.config, "Golden Depth Texture");
+
+    let mut encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Golden Encoder"),
+    });
+    // Start from a known depth and colour, as `BasicPass` loads rather than clears
+    drop(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Golden Clear Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &target.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &depth_texture.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }),
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    }));
+    let encoder = draw(&app_data, &target.view, encoder, &depth_texture);
+    app_data.queue.submit(std::iter::once(encoder.finish()));
+
+    app_data.read_target().unwrap()
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("golden")
+}
+
+// Returns the number of pixels with any channel differing by more than `tolerance`, and an image highlighting them in red
+fn diff_images(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    tolerance: u8,
+) -> (usize, image::RgbaImage) {
+    let mut mismatched = 0;
+    let diff = image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        let max_difference = a.0.iter().zip(e.0.iter()).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+        if max_difference > tolerance {
+            mismatched += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            // Faded copy of the expected image, so it's clear where the differences are
+            image::Rgba([e[0] / 4, e[1] / 4, e[2] / 4, 255])
+        }
+    });
+    (mismatched, diff)
+}
+
+fn assert_matches_golden(
+    name: &str,
+    actual: &image::RgbaImage,
+) {
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&golden_path)
+        .unwrap_or_else(|e| panic!("Couldn't open {:?} ({}), run with GOLDEN_UPDATE=1 to create it", golden_path, e))
+        .to_rgba8();
+    assert_eq!(actual.dimensions(), expected.dimensions(), "{} doesn't match the size of its reference image", name);
+
+    let (mismatched, diff) = diff_images(actual, &expected, CHANNEL_TOLERANCE);
+    let allowed = (MAX_MISMATCHED_FRACTION * (WIDTH * HEIGHT) as f64) as usize;
+    if mismatched > allowed {
+        let output_dir = output_dir();
+        std::fs::create_dir_all(&output_dir).unwrap();
+        actual.save(output_dir.join(format!("{}-actual.png", name))).unwrap();
+        diff.save(output_dir.join(format!("{}-diff.png", name))).unwrap();
+        panic!(
+            "{} differs from its reference in {} pixels (allowed {}), see {:?}",
+            name, mismatched, allowed, output_dir,
+        );
+    }
+}
+
+fn render_phong(
+    camera: &Camera,
+    instances: Vec<Instance>,
+) -> image::RgbaImage {
+    render(|app_data, view, encoder, depth_texture| {
+        let mut phong_pass = PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, camera);
+        let model = common::load_model(app_data, "cube.obj", &phong_pass.texture_bind_group_layout);
+        let objects = vec![Object::new(model, instances)];
+        phong_pass.draw(app_data, view, encoder, &objects, Some(depth_texture)).unwrap()
+    })
+}
+
+#[test]
+fn phong_pass_matches_golden() {
+    assert_matches_golden("phong", &render_phong(&camera(), instances()));
+}
+
+// Nothing in the scene is anywhere near the far plane, so dropping it and reversing depth shouldn't change the image
+#[test]
+fn infinite_perspective_matches_perspective_golden() {
+    let camera = camera().with_projection(Projection::InfinitePerspective);
+    assert_matches_golden("phong", &render_phong(&camera, instances()));
+}
+
+#[test]
+fn orthographic_phong_pass_matches_golden() {
+    let camera = camera().with_projection(Projection::Orthographic { size: 12.0 });
+    assert_matches_golden("phong_orthographic", &render_phong(&camera, instances()));
+}
+
+// Squashed, stretched and tinted differently along each row, which also checks the lighting follows the scaled normals
+#[test]
+fn scaled_tinted_phong_pass_matches_golden() {
+    let instances = instances().into_iter().enumerate().map(|(i, instance)| {
+        let scales = [[1.0, 1.0, 1.0], [1.5, 0.5, 1.0], [0.5, 1.0, 2.0]];
+        let tints = [[1.0, 0.3, 0.3, 1.0], [0.3, 1.0, 0.3, 1.0], [0.3, 0.3, 1.0, 1.0]];
+        Instance { scale: scales[i / 3].into(), tint: tints[i % 3], ..instance }
+    }).collect();
+    assert_matches_golden("phong_scaled_tinted", &render_phong(&camera(), instances));
+}
+
+const ANIMATION_STEPS: usize = 30;
+const ANIMATION_DELTA_TIME: f32 = 1.0 / 30.0;
+
+fn moving_instances() -> Vec<Instance> {
+    instances().into_iter().enumerate().map(|(i, instance)| Instance {
+        rotation_speed: 90.0 + i as f32 * 20.0,
+        velocity: cgmath::Vector3::new(0.0, 0.5 - (i % 3) as f32 * 0.5, 0.0),
+        scale: cgmath::Vector3::new(1.0, 0.5 + (i / 3) as f32 * 0.25, 1.0),
+        // The last row hangs from a tilted, stretched parent, as if attached to a scene node
+        parent_transform: if i % 3 == 2 {
+            cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.0, 1.0, 0.0))
+                * cgmath::Matrix4::from_angle_z(cgmath::Deg(20.0))
+                * cgmath::Matrix4::from_nonuniform_scale(1.0, 1.0, 0.75)
+        } else {
+            cgmath::Matrix4::identity()
+        },
+        ..instance
+    }).collect()
+}
+
+fn animate_on_cpu(
+    instances: &mut [Instance],
+) {
+    for _ in 0..ANIMATION_STEPS {
+        for instance in instances.iter_mut() {
+            instance.update(ANIMATION_DELTA_TIME);
+        }
+    }
+}
+
+// Runs `AnimationPass` as a frame would, once per step, clearing the changes it has taken in each time
+fn animate_on_gpu(
+    app_data: &AppData,
+    encoder: &mut wgpu::CommandEncoder,
+    phong_pass: &mut PhongPass,
+    animation_pass: &mut AnimationPass,
+    objects: &mut [Object],
+) {
+    for _ in 0..ANIMATION_STEPS {
+        phong_pass.update_instance_buffers(app_data, objects);
+        animation_pass.dispatch(app_data, encoder, objects, &phong_pass.instance_buffers, ANIMATION_DELTA_TIME);
+        for object in objects.iter_mut() {
+            object.clear_dirty();
+        }
+    }
+}
+
+// Unlike `assert_matches_golden`, compares two frames rendered by the test rather than one against a reference image
+fn assert_images_match(
+    name: &str,
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+) {
+    let (mismatched, diff) = diff_images(actual, expected, CHANNEL_TOLERANCE);
+    let allowed = (MAX_MISMATCHED_FRACTION * (WIDTH * HEIGHT) as f64) as usize;
+    if mismatched > allowed {
+        let output_dir = output_dir();
+        std::fs::create_dir_all(&output_dir).unwrap();
+        actual.save(output_dir.join(format!("{}-actual.png", name))).unwrap();
+        expected.save(output_dir.join(format!("{}-expected.png", name))).unwrap();
+        diff.save(output_dir.join(format!("{}-diff.png", name))).unwrap();
+        panic!("{} differs in {} pixels (allowed {}), see {:?}", name, mismatched, allowed, output_dir);
+    }
+}
+
+// Moving and spinning the instances on the GPU should end up where `Instance::update` puts them on the CPU
+#[test]
+fn gpu_animation_matches_cpu_animation() {
+    let mut cpu_instances = moving_instances();
+    animate_on_cpu(&mut cpu_instances);
+    let expected = render_phong(&camera(), cpu_instances);
+
+    let camera = camera();
+    let actual = render(|app_data, view, mut encoder, depth_texture| {
+        let mut phong_pass = PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+        let mut animation_pass = AnimationPass::new(&app_data.device).unwrap();
+        let model = common::load_model(app_data, "cube.obj", &phong_pass.texture_bind_group_layout);
+        let mut objects = vec![Object::new(model, moving_instances())];
+        animate_on_gpu(app_data, &mut encoder, &mut phong_pass, &mut animation_pass, &mut objects);
+        phong_pass.draw(app_data, view, encoder, &objects, Some(depth_texture)).unwrap()
+    });
+    assert_images_match("gpu_animation", &actual, &expected);
+}
+
+// Culling goes by where the CPU last put each instance, so an instance the GPU moves into view from outside it is only
+// drawn with culling turned off for its object
+#[test]
+fn gpu_animation_draws_instances_moved_into_view() {
+    let incoming = || Instance {
+        position: cgmath::Vector3::new(14.0, 0.0, 0.0),
+        velocity: cgmath::Vector3::new(-12.0, 0.0, 0.0),
+        ..Default::default()
+    };
+    // Well clear of the view, even allowing for the cube's corners
+    let cube_sphere = BoundingSphere { center: cgmath::Point3::origin(), radius: 2.0 };
+    assert!(!camera().frustum().intersects_sphere(&incoming().bounding_sphere(&cube_sphere)));
+
+    let mut cpu_instances = moving_instances();
+    cpu_instances.push(incoming());
+    animate_on_cpu(&mut cpu_instances);
+    let expected = render_phong(&camera(), cpu_instances);
+
+    let camera = camera();
+    let actual = render(|app_data, view, mut encoder, depth_texture| {
+        let mut phong_pass = PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+        let mut animation_pass = AnimationPass::new(&app_data.device).unwrap();
+        let model = common::load_model(app_data, "cube.obj", &phong_pass.texture_bind_group_layout);
+        let mut instances = moving_instances();
+        instances.push(incoming());
+        let mut objects = vec![Object::new(model, instances)];
+        objects[0].frustum_culling = false;
+        animate_on_gpu(app_data, &mut encoder, &mut phong_pass, &mut animation_pass, &mut objects);
+        phong_pass.draw(app_data, view, encoder, &objects, Some(depth_texture)).unwrap()
+    });
+    assert_images_match("gpu_animation_into_view", &actual, &expected);
+}
+
+// Removing instances moves others into their places, and they should carry on from where the GPU had got them to, as
+// should an instance added at the same time from where the CPU put it
+#[test]
+fn gpu_animation_follows_removed_and_added_instances() {
+    let added = || Instance {
+        position: cgmath::Vector3::new(0.0, 2.0, 0.0),
+        rotation_speed: 45.0,
+        ..Default::default()
+    };
+
+    let mut cpu_instances = moving_instances();
+    animate_on_cpu(&mut cpu_instances);
+    cpu_instances.swap_remove(0);
+    cpu_instances.swap_remove(4);
+    cpu_instances.push(added());
+    animate_on_cpu(&mut cpu_instances);
+    let expected = render_phong(&camera(), cpu_instances);
+
+    let camera = camera();
+    let actual = render(|app_data, view, mut encoder, depth_texture| {
+        let mut phong_pass = PhongPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+        let mut animation_pass = AnimationPass::new(&app_data.device).unwrap();
+        let model = common::load_model(app_data, "cube.obj", &phong_pass.texture_bind_group_layout);
+        let mut objects = vec![Object::new(model, moving_instances())];
+        // The changes reach the GPU as soon as they're submitted, so the steps before them go in a submission of their own
+        let mut first_encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        animate_on_gpu(app_data, &mut first_encoder, &mut phong_pass, &mut animation_pass, &mut objects);
+        app_data.queue.submit(std::iter::once(first_encoder.finish()));
+
+        let removed = [objects[0].handle_at(0), objects[0].handle_at(4)];
+        for handle in removed {
+            objects[0].remove_instance(handle).unwrap();
+        }
+        objects[0].push_instance(added());
+        animate_on_gpu(app_data, &mut encoder, &mut phong_pass, &mut animation_pass, &mut objects);
+        phong_pass.draw(app_data, view, encoder, &objects, Some(depth_texture)).unwrap()
+    });
+    assert_images_match("gpu_animation_removal", &actual, &expected);
+}
+
+#[test]
+fn basic_pass_matches_golden() {
+    let camera = camera();
+    let image = render(|app_data, view, encoder, depth_texture| {
+        let mut basic_pass = BasicPass::new(&app_data.device, &app_data.queue, &app_data.config, &camera);
+        basic_pass.light_uniform.position = [0.0, 1.0, 0.0];
+        basic_pass.light_uniform.color = [1.0, 0.8, 0.2];
+        // `BasicPass` doesn't use materials, but `load_model` needs a layout to create them with
+        let texture_bind_group_layout = app_data.device.create_bind_group_layout(&hello_wgpu::model::Material::describe());
+        let model = common::load_model(app_data, "lightbulb_2.obj", &texture_bind_group_layout);
+        // The bulb model is huge, so it's scaled down to sit on the light
+        let objects = vec![Object::new(model, vec![Instance {
+            position: cgmath::Vector3::new(0.0, 1.0, 0.0),
+            rotation: cgmath::Quaternion::one(),
+            rotation_speed: 0.0,
+            scale: cgmath::Vector3::new(0.0025, 0.0025, 0.0025),
+            ..Default::default()
+        }])];
+        basic_pass.draw(app_data, view, encoder, &objects, Some(depth_texture)).unwrap()
+    });
+    assert_matches_golden("basic", &image);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use hello_wgpu::{
+    camera::Camera,
+    instance::Instance,
+    object::Object,
+    render_pass::{id::IdPass, RenderPass},
+    texture::Texture,
+};
+
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 100;
+
+fn instance(
+    x: f32,
+    z: f32,
+) -> Instance {
+    Instance {
+        position: cgmath::Vector3::new(x, 0.0, z),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn reads_back_nearest_instance_under_pixel() {
+    let app_data = common::app_data_sized(WIDTH, HEIGHT);
+    let load_cube = || common::cube_model(&app_data);
+    // An empty object first, to check it doesn't throw the indices of the ones after it off
+    let objects = vec![
+        Object::new(load_cube(), Vec::new()),
+        // Drawn first but further away, so hidden behind the next object's cube in the middle of the screen
+        Object::new(load_cube(), vec![instance(0.0, -4.0), instance(-4.0, 0.0)]),
+        Object::new(load_cube(), vec![instance(4.0, 0.0), instance(0.0, 0.0)]),
+    ];
+    let camera = Camera::new(
+        cgmath::Point3::new(0.0, 0.0, 10.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        WIDTH as f32 / HEIGHT as f32,
+        45.0,
+        0.1,
+        100.0,
+    );
+
+    let mut id_pass = IdPass::new(&app_data.device, &camera);
+    let id_texture = Texture::create_id_texture(&app_data.device, &app_data.config, "Test ID Texture");
+    let depth_texture = Texture::create_depth_texture(&app_data.device, &app_data.config, "Test ID Depth Texture");
+    let encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Test ID Encoder"),
+    });
+    let encoder = id_pass.draw(&app_data, &id_texture.view, encoder, &objects, Some(&depth_texture)).unwrap();
+    app_data.queue.submit(std::iter::once(encoder.finish()));
+
+    let read = |x, y| id_pass.read_id(&app_data.device, &app_data.queue, &id_texture, x, y).wait(&app_data.device);
+    assert_eq!(read(WIDTH / 2, HEIGHT / 2), Some((2, 1)));
+    // Cubes 4 units either side of the centre land about a quarter of the way in from each edge
+    assert_eq!(read(WIDTH / 4, HEIGHT / 2), Some((1, 1)));
+    assert_eq!(read(WIDTH * 3 / 4, HEIGHT / 2), Some((2, 0)));
+    assert_eq!(read(WIDTH / 2, 0), None);
+}
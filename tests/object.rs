@@ -0,0 +1,168 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{app_data, cube_object};
+use hello_wgpu::{
+    camera::Camera,
+    instance::{Instance, InstanceBuffer},
+    object::InstanceOrigin,
+    render_pass::id::IdPass,
+    texture::Texture,
+};
+
+#[test]
+fn dirty_ranges_cover_every_change_until_cleared() {
+    let app_data = app_data();
+    let mut object = cube_object(&app_data, 100);
+    // Everything needs uploading to begin with
+    assert_eq!(object.dirty_ranges(), vec![0..100]);
+
+    object.clear_dirty();
+    assert_eq!(object.dirty_ranges(), vec![]);
+
+    // Changes close together share a range, but far apart they get their own
+    for index in [7, 3, 63, 64, 65] {
+        object.instance_mut(index).rotation_speed = 10.0;
+    }
+    assert_eq!(object.dirty_ranges(), vec![3..8, 63..66]);
+
+    object.clear_dirty();
+    object.push_instance(Instance::default());
+    assert_eq!(object.dirty_ranges(), vec![100..101]);
+}
+
+#[test]
+fn objects_keep_their_ids() {
+    let app_data = app_data();
+    let mut objects = [cube_object(&app_data, 1), cube_object(&app_data, 1)];
+    let ids = [objects[0].id(), objects[1].id()];
+    assert_ne!(ids[0], ids[1]);
+    objects.reverse();
+    assert_eq!([objects[1].id(), objects[0].id()], ids);
+}
+
+#[test]
+fn instance_buffer_grows_with_object() {
+    let app_data = app_data();
+    let mut object = cube_object(&app_data, 2);
+    let mut instance_buffer = InstanceBuffer::new(&app_data.device, &object, "Test Instance Buffer");
+    object.clear_dirty();
+    for _ in 0..5 {
+        object.push_instance(Instance::default());
+        instance_buffer.update(&app_data.device, &app_data.queue, &object);
+        object.clear_dirty();
+        assert_eq!(instance_buffer.len(), object.instances().len());
+        assert!(instance_buffer.buffer().size() >= InstanceBuffer::offset(instance_buffer.len()));
+    }
+    // Doubling from 2 fits 7 instances in 8, rather than reallocating for every one
+    assert_eq!(instance_buffer.buffer().size(), InstanceBuffer::offset(8));
+}
+
+#[test]
+fn handles_follow_instances_moved_by_removal() {
+    let app_data = app_data();
+    let mut object = cube_object(&app_data, 100);
+    for (i, instance) in object.instances_mut().iter_mut().enumerate() {
+        instance.rotation_speed = i as f32;
+    }
+    let handles = (0..100).map(|index| object.handle_at(index)).collect::<Vec<_>>();
+    object.clear_dirty();
+
+    // The last instance moves into the gap, and only it needs uploading again
+    let removed = object.remove_instance(handles[10]).unwrap();
+    assert_eq!(removed.rotation_speed, 10.0);
+    assert_eq!(object.instances().len(), 99);
+    assert_eq!(object.index_of(handles[99]), Some(10));
+    assert_eq!(object.get(handles[99]).unwrap().rotation_speed, 99.0);
+    assert_eq!(object.dirty_ranges(), vec![10..11]);
+    assert_eq!(object.instance_origins(), vec![(10, InstanceOrigin::Index(99))]);
+
+    // A removed instance's handle stays invalid, even once its slot is reused
+    assert!(object.remove_instance(handles[10]).is_none());
+    let added = object.push_instance(Instance::default());
+    assert!(object.get(handles[10]).is_none());
+    assert_eq!(object.index_of(added), Some(99));
+    assert_eq!(object.instance_origins(), vec![(10, InstanceOrigin::Index(99)), (99, InstanceOrigin::New)]);
+
+    // Removing the added instance again leaves nothing to move
+    object.remove_instance(added);
+    assert_eq!(object.instance_origins(), vec![(10, InstanceOrigin::Index(99))]);
+
+    object.clear_dirty();
+    assert_eq!(object.instance_origins(), vec![]);
+    assert_eq!(object.get(handles[0]).unwrap().rotation_speed, 0.0);
+}
+
+// Objects built from the same instances hand out handles to the same slots, but each only answers to its own
+#[test]
+fn handles_only_work_with_their_own_object() {
+    let app_data = app_data();
+    let mut objects = [cube_object(&app_data, 3), cube_object(&app_data, 3)];
+    let handle = objects[0].handle_at(1);
+    assert_eq!(objects[0].index_of(handle), Some(1));
+    assert_eq!(objects[1].index_of(handle), None);
+    assert!(objects[1].get_mut(handle).is_none());
+    assert!(objects[1].remove_instance(handle).is_none());
+    assert_eq!(objects[1].instances().len(), 3);
+}
+
+#[test]
+fn revision_only_changes_with_the_instances() {
+    let app_data = app_data();
+    let mut object = cube_object(&app_data, 10);
+    let revision = object.revision();
+    // Reading, and starting the next frame, leave the instances as they were
+    let _ = object.instances()[3].bounding_sphere(&object.model.bounding_sphere);
+    object.clear_dirty();
+    assert_eq!(object.revision(), revision);
+
+    object.instance_mut(3).rotation_speed = 10.0;
+    let revision_after_change = object.revision();
+    assert_ne!(revision_after_change, revision);
+    object.remove_instance(object.handle_at(0));
+    assert_ne!(object.revision(), revision_after_change);
+}
+
+// A pass that skips a frame misses the changes the other passes cleared in it, so its buffer has to start over
+#[test]
+fn instance_buffer_catches_up_after_missing_a_clear() {
+    const WIDTH: u32 = 200;
+    const HEIGHT: u32 = 100;
+    let app_data = common::app_data_sized(WIDTH, HEIGHT);
+    let mut object = cube_object(&app_data, 1);
+    let mut instance_buffer = InstanceBuffer::new(&app_data.device, &object, "Test Instance Buffer");
+    object.clear_dirty();
+
+    // Moved in one frame and left alone in the next, with the buffer updated in neither
+    object.instance_mut(0).position = cgmath::Vector3::new(4.0, 0.0, 0.0);
+    object.clear_dirty();
+    object.clear_dirty();
+    assert_eq!(object.dirty_ranges(), vec![]);
+    instance_buffer.update(&app_data.device, &app_data.queue, &object);
+
+    // Drawing IDs from the buffer shows where the instance is on the GPU
+    let camera = Camera::new(
+        cgmath::Point3::new(0.0, 0.0, 10.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        WIDTH as f32 / HEIGHT as f32,
+        45.0,
+        0.1,
+        100.0,
+    );
+    let mut id_pass = IdPass::new(&app_data.device, &camera);
+    let id_texture = Texture::create_id_texture(&app_data.device, &app_data.config, "Test ID Texture");
+    let depth_texture = Texture::create_depth_texture(&app_data.device, &app_data.config, "Test ID Depth Texture");
+    let encoder = app_data.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Test ID Encoder"),
+    });
+    let objects = [object];
+    let instance_buffers = HashMap::from([(objects[0].id(), instance_buffer)]);
+    let encoder = id_pass.draw_with_instance_buffers(&app_data, &id_texture.view, encoder, &objects, Some(&depth_texture), &instance_buffers).unwrap();
+    app_data.queue.submit(std::iter::once(encoder.finish()));
+
+    let read = |x, y| id_pass.read_id(&app_data.device, &app_data.queue, &id_texture, x, y).wait(&app_data.device);
+    assert_eq!(read(WIDTH * 3 / 4, HEIGHT / 2), Some((0, 0)));
+    assert_eq!(read(WIDTH / 2, HEIGHT / 2), None);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use cgmath::prelude::*;
+use hello_wgpu::{
+    camera::{Camera, Projection},
+    instance::Instance,
+    object::Object,
+    picking::{self, PickPrecision, Ray},
+};
+
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 100;
+
+fn camera() -> Camera {
+    Camera::new(
+        cgmath::Point3::new(0.0, 0.0, 10.0),
+        cgmath::Point3::new(0.0, 0.0, 0.0),
+        cgmath::Vector3::unit_y(),
+        WIDTH as f32 / HEIGHT as f32,
+        45.0,
+        0.1,
+        100.0,
+    )
+}
+
+#[test]
+fn screen_ray_through_centre_points_at_target() {
+    for projection in [Projection::Perspective, Projection::InfinitePerspective, Projection::Orthographic { size: 10.0 }] {
+        let ray = camera().with_projection(projection).screen_ray(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0, WIDTH, HEIGHT).unwrap();
+        assert!((ray.direction - -cgmath::Vector3::unit_z()).magnitude() < 1e-4, "{:?} ray went {:?}", projection, ray.direction);
+        assert!(ray.origin.x.abs() < 1e-4 && ray.origin.y.abs() < 1e-4, "{:?} ray started at {:?}", projection, ray.origin);
+    }
+    // Pixels run down the screen, so the top row points upwards
+    let ray = camera().screen_ray(WIDTH as f32 / 2.0, 0.0, WIDTH, HEIGHT).unwrap();
+    assert!(ray.direction.y > 0.0);
+}
+
+#[test]
+fn ray_hits_triangle_from_either_side() {
+    let triangle = [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]].map(cgmath::Point3::from);
+    for (origin, direction) in [([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]), ([0.0, 0.0, -5.0], [0.0, 0.0, 1.0])] {
+        let ray = Ray::new(origin.into(), direction.into());
+        assert_eq!(ray.intersect_triangle(triangle[0], triangle[1], triangle[2]), Some(5.0));
+    }
+    let miss = Ray::new(cgmath::Point3::new(3.0, 0.0, 5.0), -cgmath::Vector3::unit_z());
+    assert_eq!(miss.intersect_triangle(triangle[0], triangle[1], triangle[2]), None);
+}
+
+#[test]
+fn pick_finds_nearest_instance() {
+    let model = common::cube_model(&common::app_data());
+    // Two cubes one behind the other along the view direction, and one off to the side
+    let instances = [[0.0, 0.0, 0.0], [0.0, 0.0, -4.0], [4.0, 0.0, 0.0]].map(|position| Instance {
+        position: position.into(),
+        rotation: cgmath::Quaternion::one(),
+        rotation_speed: 0.0,
+        ..Default::default()
+    });
+    let objects = vec![Object::new(model, instances.into())];
+
+    let ray = Ray::new(cgmath::Point3::new(0.0, 0.0, 10.0), -cgmath::Vector3::unit_z());
+    let (object_index, instance_index, hit_point) = picking::pick(&ray, &objects, PickPrecision::Mesh).unwrap();
+    assert_eq!((object_index, instance_index), (0, 0));
+    // The cube's front face is at z = 1
+    assert!((hit_point.z - 1.0).abs() < 1e-4, "hit at {:?}", hit_point);
+
+    // The bounding sphere sticks out past the face
+    let (_, instance_index, sphere_hit_point) = picking::pick(&ray, &objects, PickPrecision::BoundingSphere).unwrap();
+    assert_eq!(instance_index, 0);
+    assert!(sphere_hit_point.z > hit_point.z);
+
+    let ray = Ray::new(cgmath::Point3::new(4.0, 0.0, 10.0), -cgmath::Vector3::unit_z());
+    assert_eq!(picking::pick(&ray, &objects, PickPrecision::Mesh).map(|(_, instance_index, _)| instance_index), Some(2));
+
+    let ray = Ray::new(cgmath::Point3::new(10.0, 10.0, 10.0), -cgmath::Vector3::unit_z());
+    assert_eq!(picking::pick(&ray, &objects, PickPrecision::Mesh), None);
+}
+
+#[test]
+fn pick_hits_scaled_instance() {
+    let model = common::cube_model(&common::app_data());
+    // Stretched out to x = ±3 and towards the camera to z = 2
+    let instance = Instance {
+        scale: cgmath::Vector3::new(3.0, 1.0, 2.0),
+        ..Default::default()
+    };
+    let objects = vec![Object::new(model, vec![instance])];
+
+    let ray = Ray::new(cgmath::Point3::new(2.5, 0.0, 10.0), -cgmath::Vector3::unit_z());
+    let (_, _, hit_point) = picking::pick(&ray, &objects, PickPrecision::Mesh).unwrap();
+    assert!((hit_point.z - 2.0).abs() < 1e-4, "hit at {:?}", hit_point);
+
+    let ray = Ray::new(cgmath::Point3::new(0.0, 1.5, 10.0), -cgmath::Vector3::unit_z());
+    assert_eq!(picking::pick(&ray, &objects, PickPrecision::Mesh), None);
+}
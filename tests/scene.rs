@@ -0,0 +1,70 @@
+mod common;
+
+use cgmath::prelude::*;
+use common::{app_data, cube_object};
+use hello_wgpu::scene::{SceneGraph, Transform};
+
+fn assert_near(
+    actual: cgmath::Point3<f32>,
+    expected: cgmath::Point3<f32>,
+) {
+    assert!(actual.distance(expected) < 1e-5, "expected {:?}, got {:?}", expected, actual);
+}
+
+#[test]
+fn world_matrices_follow_their_ancestors() {
+    let mut scene = SceneGraph::new();
+    let pivot = scene.add_node(None, Transform::default());
+    let arm = scene.add_node(Some(pivot), Transform { position: cgmath::Vector3::new(2.0, 0.0, 0.0), ..Default::default() });
+    let tip = scene.add_node(Some(arm), Transform { position: cgmath::Vector3::new(0.0, 1.0, 0.0), ..Default::default() });
+    scene.update([]);
+    assert_near(scene.world_position(tip), cgmath::Point3::new(2.0, 1.0, 0.0));
+
+    // Turning the pivot a quarter turn about y swings the arm from +x round to -z, and the tip with it
+    scene.transform_mut(pivot).rotation = cgmath::Quaternion::from_angle_y(cgmath::Deg(90.0));
+    scene.update([]);
+    assert_near(scene.world_position(arm), cgmath::Point3::new(0.0, 0.0, -2.0));
+    assert_near(scene.world_position(tip), cgmath::Point3::new(0.0, 1.0, -2.0));
+
+    // Moving the tip to the top of the graph leaves it where its own transform puts it
+    scene.set_parent(tip, None);
+    scene.update([]);
+    assert_near(scene.world_position(tip), cgmath::Point3::new(0.0, 1.0, 0.0));
+    assert_eq!(scene.children(arm), &[]);
+}
+
+#[test]
+fn update_only_touches_instances_under_changed_nodes() {
+    let mut object = cube_object(&app_data(), 40);
+    let mut scene = SceneGraph::new();
+    let group = scene.add_node(None, Transform::default());
+    let other = scene.add_node(None, Transform::default());
+    for i in 0..10 {
+        scene.attach(group, &object, object.handle_at(i));
+    }
+    scene.attach(other, &object, object.handle_at(30));
+    scene.update([&mut object]);
+    object.clear_dirty();
+
+    scene.transform_mut(group).position = cgmath::Vector3::new(0.0, 5.0, 0.0);
+    scene.update([&mut object]);
+    assert_eq!(object.dirty_ranges(), vec![0..10]);
+    // Each instance keeps its own position, now relative to the group
+    let sphere = object.instances()[3].bounding_sphere(&object.model.bounding_sphere);
+    assert_near(sphere.center, cgmath::Point3::new(3.0, 5.0, 0.0) + object.model.bounding_sphere.center.to_vec());
+    assert_eq!(object.instances()[3].parent_transform, scene.world_matrix(group));
+
+    // Nothing changed, so nothing is touched
+    object.clear_dirty();
+    scene.update([&mut object]);
+    assert_eq!(object.dirty_ranges(), vec![]);
+}
+
+#[test]
+#[should_panic]
+fn parenting_a_node_to_its_descendant_panics() {
+    let mut scene = SceneGraph::new();
+    let parent = scene.add_node(None, Transform::default());
+    let child = scene.add_node(Some(parent), Transform::default());
+    scene.set_parent(parent, Some(child));
+}
@@ -0,0 +1,47 @@
+use hello_wgpu::{app::AppData, screenshot};
+
+#[test]
+fn rows_are_padded_to_the_copy_alignment() {
+    // 64 RGBA pixels are exactly 256 bytes, so widths that are multiples of 64 need no padding
+    assert_eq!(screenshot::padded_bytes_per_row(64 * 4), 256);
+    assert_eq!(screenshot::padded_bytes_per_row(128 * 4), 512);
+    assert_eq!(screenshot::padded_bytes_per_row(63 * 4), 256);
+    assert_eq!(screenshot::padded_bytes_per_row(65 * 4), 512);
+    assert_eq!(screenshot::padded_bytes_per_row(4), 256);
+}
+
+// 63 pixels wide, so every row of the copy carries 4 bytes of padding that must be stripped
+#[test]
+fn read_texture_strips_row_padding() {
+    const WIDTH: u32 = 63;
+    const HEIGHT: u32 = 7;
+    let app_data = pollster::block_on(AppData::new_headless(WIDTH, HEIGHT, wgpu::TextureFormat::Rgba8Unorm)).unwrap();
+    let size = wgpu::Extent3d { width: WIDTH, height: HEIGHT, depth_or_array_layers: 1 };
+    let texture = app_data.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Readback Test Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    // Every pixel is different, so a row read back from the wrong offset shows up
+    let expected = image::RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| image::Rgba([x as u8, y as u8, (x * 3 + y) as u8, 255]));
+    app_data.queue.write_texture(
+        texture.as_image_copy(),
+        &expected,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(WIDTH * 4),
+            rows_per_image: Some(HEIGHT),
+        },
+        size,
+    );
+
+    let actual = screenshot::read_texture(&app_data.device, &app_data.queue, &texture).unwrap();
+    assert_eq!(actual.dimensions(), (WIDTH, HEIGHT));
+    assert_eq!(*actual.get_pixel(62, 6), image::Rgba([62, 6, 192, 255]));
+    assert!(actual == expected);
+}
@@ -0,0 +1,52 @@
+use hello_wgpu::timestep::FixedTimestep;
+
+// 4 ticks per second, so each step is a quarter of a second and the sums below are exact
+const TICK_RATE: f64 = 4.0;
+
+#[test]
+fn accumulate_hands_out_whole_steps() {
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    assert_eq!(timestep.step(), 0.25);
+
+    // Less than a step runs nothing, but is kept for the next frame
+    assert_eq!(timestep.accumulate(0.125), 0);
+    assert_eq!(timestep.accumulate(0.125), 1);
+    assert_eq!(timestep.accumulate(0.75), 3);
+    assert_eq!(timestep.accumulate(0.5 + 0.125), 2);
+    assert_eq!(timestep.accumulate(0.125), 1);
+}
+
+#[test]
+fn accumulate_drops_time_beyond_max_steps_per_frame() {
+    let mut timestep = FixedTimestep::new(TICK_RATE).with_max_steps_per_frame(3);
+
+    // A 10 second frame only runs 3 steps, and the rest is dropped rather than run over the following frames
+    assert_eq!(timestep.accumulate(10.0 + 0.125), 3);
+    assert_eq!(timestep.alpha(), 0.5);
+    assert_eq!(timestep.accumulate(0.0), 0);
+    assert_eq!(timestep.accumulate(0.125), 1);
+}
+
+#[test]
+fn alpha_is_how_far_into_the_next_step() {
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    assert_eq!(timestep.alpha(), 0.0);
+    timestep.accumulate(0.0625);
+    assert_eq!(timestep.alpha(), 0.25);
+    timestep.accumulate(0.25 + 0.125);
+    assert_eq!(timestep.alpha(), 0.75);
+    timestep.accumulate(0.0625);
+    assert_eq!(timestep.alpha(), 0.0);
+}
+
+#[test]
+#[should_panic]
+fn zero_tick_rate_panics() {
+    FixedTimestep::new(0.0);
+}
+
+#[test]
+#[should_panic]
+fn negative_tick_rate_panics() {
+    FixedTimestep::new(-60.0);
+}